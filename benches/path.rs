@@ -0,0 +1,225 @@
+// Compares the A* search of path::find with the previous implementation,
+// which scanned the whole open list for the lowest f value on every
+// iteration. Run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+
+use snake::game::Game;
+use snake::path;
+
+// Grid sizes and the number of searches to run on each of them.
+const GRIDS: [(usize, usize, u64); 4] = [(20, 15, 200), (60, 30, 50), (120, 60, 10), (200, 100, 3)];
+
+fn main() {
+    println!(
+        "{:>10} {:>10} {:>14} {:>14} {:>9}",
+        "grid", "searches", "old (scan)", "new (heap)", "speedup"
+    );
+
+    for (width, height, searches) in GRIDS {
+        let mut old = Duration::ZERO;
+        let mut new = Duration::ZERO;
+
+        for seed in 0..searches {
+            let game = Game::new(width, height, true, false, seed);
+            let (head, food) = (*game.head(), game.food().unwrap());
+
+            let start = Instant::now();
+            let old_path = old::find(game.grid(), head, food);
+            old += start.elapsed();
+
+            let start = Instant::now();
+            let new_path = path::find(game.grid(), head, food, false);
+            new += start.elapsed();
+
+            assert_eq!(old_path, new_path, "paths differ for seed {}", seed);
+        }
+
+        println!(
+            "{:>10} {:>10} {:>14?} {:>14?} {:>8.1}x",
+            format!("{}x{}", width, height),
+            searches,
+            old / searches as u32,
+            new / searches as u32,
+            old.as_secs_f64() / new.as_secs_f64()
+        );
+    }
+}
+
+// The pathfinding as it was before the open list became a binary heap,
+// copied from src/path.rs of that time. Only the wrap arguments, which
+// generate_successors() and next_point() didn't have then, are new.
+mod old {
+    use snake::game::{generate_successors, next_point};
+    use snake::types::{Direction, Grid, Point, Tile};
+
+    // Calculates a path from the start position to the target on the grid using the A* Search Algorithm.
+    // The result is a vector of directions. If no path can be found an empty vector is returned.
+    //
+    // --> https://www.geeksforgeeks.org/a-search-algorithm/
+    // g: The movement cost to move from the starting point to this point on the grid,
+    //    following the path generated to get there.
+    // h: The estimated movement cost to move from this point on the grid to the final destination.
+    //    We currently use manhatten distance as an approximation heuristic.
+    // f: The search algorith picks the next point having the lowest 'f' and proceeds with that.
+    pub fn find(grid: &Grid, start: Point, target: Point) -> Vec<Direction> {
+        let (start_x, start_y) = start;
+        let grid_width = grid.len();
+        let grid_height = grid[0].len();
+
+        // Create a bunch of 2D arrays to hold the details of a point.
+        let mut parents = vec![vec![None; grid_height]; grid_width];
+        let mut g_list = vec![vec![0; grid_height]; grid_width];
+        let mut f_list = vec![vec![i32::MAX; grid_height]; grid_width];
+
+        // Create a closed list to hold already checked points.
+        let mut closed = vec![vec![false; grid_height]; grid_width];
+
+        // Create a open list to hold potential points of the path.
+        // A value of true mean that the point is in the open list.
+        let mut open = vec![vec![false; grid_height]; grid_width];
+
+        // Put the starting point on the open list with a f value of 0.
+        open[start_x][start_y] = true;
+        f_list[start_x][start_y] = 0;
+
+        // Pop the point with the lowest f value off the open list.
+        while let Some(p) = get_lowest_f(&mut open, &f_list) {
+            let (x, y) = p;
+
+            // Push it on the closed list.
+            closed[x][y] = true;
+
+            // Go through all successors for that point.
+            for s in generate_successors(p, grid, false).iter() {
+                let (s_x, s_y) = *s;
+
+                // Skip blocked tiles.
+                if blocked_tile(grid, *s) {
+                    continue;
+                }
+
+                // If the successor is already on the closed list, ignore it.
+                if closed[s_x][s_y] {
+                    continue;
+                }
+
+                // If successor is the target, stop and generate the path.
+                if *s == target {
+                    parents[s_x][s_y] = Some(p);
+                    return generate_path(*s, &parents);
+                }
+
+                // Compute g,h and f for the successor.
+                let g = g_list[x][y] + 1;
+                let h = manhatten_distance(*s, target);
+                let f = g + h;
+
+                // If the known f value is lower than what we currently have for the position.
+                if f < f_list[s_x][s_y] {
+                    // Update the details of this position with the values of the successor.
+                    g_list[s_x][s_y] = g;
+                    f_list[s_x][s_y] = f;
+                    parents[s_x][s_y] = Some(p);
+
+                    // And push it on the open list.
+                    open[s_x][s_y] = true;
+                }
+            }
+        }
+
+        // If we reach this point we couldn't find a clear path.
+        // We fallback to to longest free straight path.
+        best_straight_path(grid, start)
+    }
+
+    // Finds the point with the lowest f value in the list and returns it.
+    fn get_lowest_f(list: &mut [Vec<bool>], f_list: &[Vec<i32>]) -> Option<Point> {
+        let mut lowest_f = i32::MAX;
+        let mut res: Option<Point> = None;
+        for (x, y_list) in list.iter().enumerate() {
+            for (y, value) in y_list.iter().enumerate() {
+                if !(*value) {
+                    continue;
+                }
+                let f = f_list[x][y];
+                if f < lowest_f {
+                    lowest_f = f;
+                    res = Some((x, y));
+                }
+            }
+        }
+        if let Some((x, y)) = res {
+            list[x][y] = false
+        }
+        res
+    }
+
+    // Generates the path from the starting point to the target as a vector of directions.
+    // The entries are in reverse order so that a pop() on the vector returns the next direction.
+    fn generate_path(target: Point, parents: &[Vec<Option<Point>>]) -> Vec<Direction> {
+        let mut directions: Vec<Direction> = Vec::new();
+        let mut p = target;
+        loop {
+            let (x, y) = p;
+            match parents[x][y] {
+                Some(parent) => {
+                    let direction = get_direction(parent, p);
+                    directions.push(direction);
+                    p = parent;
+                }
+                None => break,
+            }
+        }
+        directions
+    }
+
+    fn best_straight_path(grid: &Grid, start: Point) -> Vec<Direction> {
+        let mut direction = None;
+        let mut count = 0;
+        for p in generate_successors(start, grid, false) {
+            let d = get_direction(start, p);
+            let mut n = p;
+            let mut c = 0;
+            while !blocked_tile(grid, n) {
+                c += 1;
+                n = next_point(n, d, grid, false);
+            }
+            if c > count {
+                count = c;
+                direction = Some(d);
+            }
+        }
+        match direction {
+            Some(d) => vec![d],
+            None => Vec::new(),
+        }
+    }
+
+    fn get_direction(from: Point, to: Point) -> Direction {
+        let (from_x, from_y) = from;
+        let (to_x, to_y) = to;
+        if to_x > from_x {
+            Direction::East
+        } else if to_x < from_x {
+            Direction::West
+        } else if to_y > from_y {
+            Direction::South
+        } else {
+            Direction::North
+        }
+    }
+
+    fn manhatten_distance(from: Point, to: Point) -> i32 {
+        let (from_x, from_y) = from;
+        let (to_x, to_y) = to;
+        let dx = (from_x as i32 - to_x as i32).abs();
+        let dy = (from_y as i32 - to_y as i32).abs();
+        dx + dy
+    }
+
+    fn blocked_tile(grid: &Grid, p: Point) -> bool {
+        let (x, y) = p;
+        grid[x][y] != Tile::Free && grid[x][y] != Tile::Food
+    }
+}
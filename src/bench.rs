@@ -0,0 +1,253 @@
+use std::fmt;
+use std::sync::atomic::{self, AtomicU64};
+use std::sync::Mutex;
+use std::thread;
+
+use snake::config::Autopilot;
+use snake::game::{Game, StepOutcome};
+use snake::strategy;
+
+// Settings of a benchmark run.
+pub struct Bench {
+    pub games: u64,
+    pub strategy: Autopilot,
+    pub width: u16,
+    pub height: u16,
+    pub obstacles: bool,
+    pub wrap: bool,
+    // Game n is played with the seed `seed + n`.
+    pub seed: u64,
+}
+
+// How a game ended.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum End {
+    // The snake fills the whole grid.
+    Won,
+    Wall,
+    Obstacle,
+    Itself,
+    // The snake went around in circles without eating for too long.
+    Starved,
+}
+
+const CAUSES: [End; 4] = [End::Wall, End::Obstacle, End::Itself, End::Starved];
+
+impl fmt::Display for End {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            End::Won => "won",
+            End::Wall => "wall",
+            End::Obstacle => "obstacle",
+            End::Itself => "self",
+            End::Starved => "starvation loop",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct GameResult {
+    length: usize,
+    steps: u32,
+    end: End,
+    // The game was played with another strategy than the benchmarked one,
+    // the cycle autopilot falls back to A* when it can't build a cycle.
+    fallback: Option<Autopilot>,
+}
+
+// Plays all games of the benchmark, spread over all cores, and prints the statistics.
+pub fn run(bench: &Bench) {
+    let (results, fallbacks): (Vec<GameResult>, Vec<GameResult>) = play_all(bench)
+        .into_iter()
+        .partition(|r| r.fallback.is_none());
+
+    println!(
+        "{} games on a {}{}x{} grid {} obstacles with the {} autopilot, seeds {}-{}",
+        bench.games,
+        if bench.wrap { "wrapping " } else { "" },
+        bench.width,
+        bench.height,
+        if bench.obstacles { "with" } else { "without" },
+        bench.strategy,
+        bench.seed,
+        bench.seed + bench.games - 1
+    );
+    if let Some(fallback) = fallbacks.first().and_then(|r| r.fallback) {
+        println!(
+            "{} games couldn't use the {} autopilot and fell back to {}, they aren't counted",
+            fallbacks.len(),
+            bench.strategy,
+            fallback
+        );
+    }
+    if results.is_empty() {
+        println!("No games left to count");
+        return;
+    }
+    print!("{}", Stats::new(&results));
+}
+
+fn play_all(bench: &Bench) -> Vec<GameResult> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let next = AtomicU64::new(0);
+    let results = Mutex::new(Vec::with_capacity(bench.games as usize));
+
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| loop {
+                let n = next.fetch_add(1, atomic::Ordering::Relaxed);
+                if n >= bench.games {
+                    break;
+                }
+                let result = play(bench, bench.seed + n);
+                results.lock().unwrap().push(result);
+            });
+        }
+    });
+
+    results.into_inner().unwrap()
+}
+
+// Plays a single game until the snake wins, crashes or starves.
+fn play(bench: &Bench, seed: u64) -> GameResult {
+    let (width, height) = (bench.width as usize, bench.height as usize);
+    let mut game = Game::new(width, height, bench.obstacles, bench.wrap, seed);
+    let (mut strategy, fallback) = strategy::create(bench.strategy, &game);
+
+    // Even the cycle autopilot reaches every tile within this many steps, so
+    // a snake that takes longer to find food is going around in circles.
+    let starvation_limit = (width * height) as u32;
+    let mut last_meal = 0;
+
+    loop {
+        let direction = strategy.next_direction(&game, 0);
+        let end = match game.step(direction) {
+            StepOutcome::Moved if game.steps() - last_meal > starvation_limit => End::Starved,
+            StepOutcome::Moved => continue,
+            StepOutcome::Ate => {
+                last_meal = game.steps();
+                continue;
+            }
+            StepOutcome::Won => End::Won,
+            StepOutcome::Crashed(p) => {
+                // The snake doesn't move when it crashes, so it still covers the tile it hit.
+                let (x, y) = p;
+                if game.snake().contains(&p) {
+                    End::Itself
+                } else if !bench.wrap && (x == 0 || y == 0 || x == width - 1 || y == height - 1) {
+                    End::Wall
+                } else {
+                    End::Obstacle
+                }
+            }
+        };
+        return GameResult {
+            length: game.snake().len(),
+            steps: game.steps(),
+            end,
+            fallback,
+        };
+    }
+}
+
+struct Stats {
+    games: usize,
+    mean_length: f64,
+    median_length: usize,
+    max_length: usize,
+    steps_per_food: f64,
+    won: usize,
+    deaths: Vec<(End, usize)>,
+}
+
+impl Stats {
+    fn new(results: &[GameResult]) -> Self {
+        let games = results.len();
+        let mut lengths: Vec<usize> = results.iter().map(|r| r.length).collect();
+        lengths.sort_unstable();
+
+        // The snake starts with a length of two.
+        let food: usize = lengths.iter().map(|l| l - 2).sum();
+        let steps: u64 = results.iter().map(|r| r.steps as u64).sum();
+        let count = |end: End| results.iter().filter(|r| r.end == end).count();
+
+        Stats {
+            games,
+            mean_length: lengths.iter().sum::<usize>() as f64 / games as f64,
+            median_length: lengths[games / 2],
+            max_length: lengths[games - 1],
+            steps_per_food: steps as f64 / food.max(1) as f64,
+            won: count(End::Won),
+            deaths: CAUSES.iter().map(|end| (*end, count(*end))).collect(),
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = |n: usize| n as f64 * 100.0 / self.games as f64;
+        writeln!(
+            f,
+            "Length:           mean {:.1}, median {}, max {}",
+            self.mean_length, self.median_length, self.max_length
+        )?;
+        writeln!(f, "Steps per food:   {:.1}", self.steps_per_food)?;
+        writeln!(
+            f,
+            "Boards completed: {} ({:.1}%)",
+            self.won,
+            percent(self.won)
+        )?;
+        writeln!(f, "Deaths:")?;
+        for (end, n) in self.deaths.iter() {
+            writeln!(f, "  {:<16} {} ({:.1}%)", end, n, percent(*n))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bench(strategy: Autopilot, obstacles: bool) -> Bench {
+        Bench {
+            games: 4,
+            strategy,
+            width: 12,
+            height: 12,
+            obstacles,
+            wrap: false,
+            seed: 1,
+        }
+    }
+
+    #[test]
+    fn play_all_games() {
+        let results = play_all(&bench(Autopilot::AStar, true));
+        assert_eq!(results.len(), 4);
+        assert!(results.iter().all(|r| r.length >= 2 && r.steps > 0));
+    }
+
+    #[test]
+    fn cycle_completes_boards() {
+        let results = play_all(&bench(Autopilot::Cycle, false));
+        let stats = Stats::new(&results);
+        assert_eq!(stats.won, 4);
+        assert_eq!(stats.max_length, 100);
+        assert!(stats.deaths.iter().all(|(_, n)| *n == 0));
+        assert!(results.iter().all(|r| r.fallback.is_none()));
+    }
+
+    #[test]
+    fn cycle_falls_back_without_cycle() {
+        // An odd number of tiles inside the border has no Hamiltonian cycle.
+        let bench = Bench {
+            width: 13,
+            height: 13,
+            ..bench(Autopilot::Cycle, false)
+        };
+        let results = play_all(&bench);
+        assert!(results.iter().all(|r| r.fallback == Some(Autopilot::AStar)));
+    }
+}
@@ -1,22 +1,33 @@
-use clap::{ArgAction, Parser};
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{ArgAction, Args, Parser, Subcommand};
+use std::path::PathBuf;
 
+use snake::config::{Autopilot, Config, MIN_INTERVAL};
+
+use crate::bench::Bench;
+use crate::keymap::PRESETS;
 use crate::output;
-use crate::snake::{self, MIN_INTERVAL};
+
+// Upper bound of the snake advance interval in ms, exclusive.
+pub const MAX_INTERVAL: i64 = 300;
 
 /// Game of snake
 #[derive(Parser)]
-#[command(disable_help_flag = true)]
+#[command(disable_help_flag = true, args_conflicts_with_subcommands = true)]
 pub struct Opts {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Snake advance interval in ms
-    #[arg(short, long, default_value_t = 175, value_parser = clap::value_parser!(u16).range(MIN_INTERVAL..300))]
+    #[arg(short, long, default_value_t = Config::default().interval, value_parser = clap::value_parser!(u16).range(MIN_INTERVAL..MAX_INTERVAL))]
     pub interval: u16,
 
     /// Width of the grid
-    #[arg(short = 'w', long, default_value_t = 20, conflicts_with = "fit_grid", value_parser = grid_width_in_range)]
+    #[arg(short = 'w', long, default_value_t = Config::default().grid_width, conflicts_with = "fit_grid", value_parser = grid_width_in_range)]
     pub grid_width: u16,
 
     /// Height of the grid
-    #[arg(short = 'h', long, default_value_t = 15, conflicts_with = "fit_grid", value_parser = grid_height_in_range)]
+    #[arg(short = 'h', long, default_value_t = Config::default().grid_height, conflicts_with = "fit_grid", value_parser = grid_height_in_range)]
     pub grid_height: u16,
 
     /// Fit the grid to the screen
@@ -27,34 +38,197 @@ pub struct Opts {
     #[arg(short = 'n', long, default_value_t = false)]
     pub no_obstacles: bool,
 
-    /// The computer controls the snake
+    /// Play on the grid of a level file instead of a random one
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["grid_width", "grid_height", "fit_grid", "no_obstacles", "record"])]
+    pub level: Option<PathBuf>,
+
+    /// Remove the border, the snake leaves the grid on one side and comes back on the other
     #[arg(long, default_value_t = false)]
-    pub autopilot: bool,
+    pub wrap: bool,
+
+    /// Two players on one keyboard, the second one steers with WASD
+    #[arg(long, default_value_t = false, conflicts_with_all = ["autopilot", "record"])]
+    pub two_player: bool,
+
+    /// Number of computer snakes competing with you for the food
+    #[arg(long, value_name = "N", default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=3), conflicts_with = "record")]
+    pub opponents: u8,
+
+    /// The computer controls the snake, using the A* search or a Hamiltonian cycle
+    #[arg(
+        long,
+        value_name = "STRATEGY",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "astar",
+        value_parser = PossibleValuesParser::new(Autopilot::names()).map(|s| s.parse::<Autopilot>().unwrap()),
+    )]
+    pub autopilot: Option<Autopilot>,
 
     /// The snake gets faster with every food eaten
     #[arg(long, default_value_t = false)]
     pub arcade: bool,
 
+    /// Seconds to count down before a game starts or resumes, 0 to start right away
+    #[arg(long, value_name = "SECONDS", default_value_t = Config::default().countdown, value_parser = clap::value_parser!(u8).range(0..=9))]
+    pub countdown: u8,
+
+    /// Number of turns remembered between two steps, so quick turns aren't lost
+    #[arg(long, value_name = "N", default_value_t = Config::default().turn_queue, value_parser = clap::value_parser!(u8).range(1..=5))]
+    pub turn_queue: u8,
+
+    /// Seed for the random number generator, to replay a grid layout
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Record the games to a replay file
+    #[arg(long, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// Name for the high-score table [default: your user name]
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Keys to steer the snake, the second player uses WASD or the arrow keys [default: arrows]
+    #[arg(long, value_name = "KEYMAP", value_parser = PossibleValuesParser::new(PRESETS))]
+    pub keymap: Option<String>,
+
+    /// Colours and characters of the grid: classic, high-contrast, colour-blind, monochrome, ascii or a theme from the config file [default: ascii if NO_COLOR is set or the locale isn't UTF-8, classic otherwise]
+    #[arg(long, value_name = "THEME")]
+    pub theme: Option<String>,
+
+    /// Print the keys of the active keymap and exit
+    #[arg(long, default_value_t = false)]
+    pub print_keys: bool,
+
+    /// Read the defaults for the options from this file [default: ~/.config/snake/config.toml]
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
     /// Print help information
-    #[arg(long = "help", action = ArgAction::Help, value_parser = clap::value_parser!(bool))]
+    #[arg(long = "help", global = true, action = ArgAction::Help, value_parser = clap::value_parser!(bool))]
     pub help: (),
 }
 
-impl From<Opts> for snake::Config {
-    fn from(opts: Opts) -> Self {
-        snake::Config {
+#[derive(Subcommand)]
+pub enum Command {
+    /// Play back a game recorded with --record
+    Replay(ReplayOpts),
+    /// Play autopilot games without a screen and print statistics
+    Bench(BenchOpts),
+    /// Show the high-score table
+    Scores(ScoresOpts),
+    /// Edit a level file for --level, it is created if it doesn't exist
+    Edit(EditOpts),
+}
+
+#[derive(Args)]
+pub struct ReplayOpts {
+    /// The replay file
+    pub file: PathBuf,
+
+    /// Number of the game in the file, starting at 1 [default: the last game]
+    #[arg(short, long)]
+    pub game: Option<usize>,
+
+    /// Start paused at the given step
+    #[arg(short, long, default_value_t = 0)]
+    pub step: u32,
+}
+
+#[derive(Args)]
+pub struct BenchOpts {
+    /// Number of games to play
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..))]
+    pub games: u64,
+
+    /// The autopilot to benchmark
+    #[arg(
+        long,
+        default_value = "astar",
+        value_parser = PossibleValuesParser::new(Autopilot::names()).map(|s| s.parse::<Autopilot>().unwrap()),
+    )]
+    pub strategy: Autopilot,
+
+    /// Width of the grid
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u16).range(output::MIN_GRID_WIDTH as i64..))]
+    pub width: u16,
+
+    /// Height of the grid
+    #[arg(long, default_value_t = 15, value_parser = clap::value_parser!(u16).range(output::MIN_GRID_HEIGHT as i64..))]
+    pub height: u16,
+
+    /// Don't place obstacles on the grid
+    #[arg(short = 'n', long, default_value_t = false)]
+    pub no_obstacles: bool,
+
+    /// Remove the border and let the snake wrap around the edges
+    #[arg(long, default_value_t = false)]
+    pub wrap: bool,
+
+    /// Seed of the first game, the following games count up from it
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+}
+
+#[derive(Args)]
+pub struct EditOpts {
+    /// The level file
+    pub file: PathBuf,
+
+    /// Width of a new level
+    #[arg(short = 'w', long, default_value_t = 20, value_parser = grid_width_in_range)]
+    pub width: u16,
+
+    /// Height of a new level
+    #[arg(short = 'h', long, default_value_t = 15, value_parser = grid_height_in_range)]
+    pub height: u16,
+}
+
+#[derive(Args)]
+pub struct ScoresOpts {
+    /// Number of scores to show per game configuration
+    #[arg(short, long, default_value_t = 10)]
+    pub count: usize,
+}
+
+impl From<&BenchOpts> for Bench {
+    fn from(opts: &BenchOpts) -> Self {
+        Bench {
+            games: opts.games,
+            strategy: opts.strategy,
+            width: opts.width,
+            height: opts.height,
+            obstacles: !opts.no_obstacles,
+            wrap: opts.wrap,
+            seed: opts.seed,
+        }
+    }
+}
+
+impl From<&Opts> for Config {
+    fn from(opts: &Opts) -> Self {
+        Config {
             autopilot: opts.autopilot,
             arcade: opts.arcade,
             grid_width: opts.grid_width,
             grid_height: opts.grid_height,
             fit_grid: opts.fit_grid,
             no_obstacles: opts.no_obstacles,
+            // Loaded by main().
+            level: None,
+            wrap: opts.wrap,
+            two_player: opts.two_player,
+            opponents: opts.opponents,
             interval: opts.interval,
+            countdown: opts.countdown,
+            turn_queue: opts.turn_queue,
+            seed: opts.seed,
         }
     }
 }
 
-fn grid_width_in_range(s: &str) -> Result<u16, String> {
+pub fn grid_width_in_range(s: &str) -> Result<u16, String> {
     let width: u16 = s.parse().map_err(|_| format!("`{}` isn't a number", s))?;
     let (max, _) = output::max_grid_size();
     if (output::MIN_GRID_WIDTH..=max).contains(&width) {
@@ -68,7 +242,7 @@ fn grid_width_in_range(s: &str) -> Result<u16, String> {
     }
 }
 
-fn grid_height_in_range(s: &str) -> Result<u16, String> {
+pub fn grid_height_in_range(s: &str) -> Result<u16, String> {
     let height: u16 = s.parse().map_err(|_| format!("`{}` isn't a number", s))?;
     let (_, max) = output::max_grid_size();
     if (output::MIN_GRID_HEIGHT..=max).contains(&height) {
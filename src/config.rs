@@ -0,0 +1,114 @@
+//! Game settings.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::level::Level;
+use crate::strategy::STRATEGIES;
+
+/// Lower bound of the snake advance interval in ms.
+pub const MIN_INTERVAL: i64 = 30;
+
+/// Settings of a game as chosen on the command line.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The computer controls the snake using the given strategy.
+    pub autopilot: Option<Autopilot>,
+    /// The snake gets faster with every food eaten.
+    pub arcade: bool,
+    /// Width of the grid, including the border.
+    pub grid_width: u16,
+    /// Height of the grid, including the border.
+    pub grid_height: u16,
+    /// Ignore the grid size and fit the grid to the screen instead.
+    pub fit_grid: bool,
+    /// Don't place obstacles on the grid.
+    pub no_obstacles: bool,
+    /// Play on the grid of this level instead of a random one. The grid
+    /// size must match the level.
+    pub level: Option<Level>,
+    /// Remove the border and let the snake wrap around the edges of the grid.
+    pub wrap: bool,
+    /// A second player steers another snake on the same grid.
+    pub two_player: bool,
+    /// Number of snakes controlled by the computer that compete with the
+    /// players for the food.
+    pub opponents: u8,
+    /// Snake advance interval in ms.
+    pub interval: u16,
+    /// Seconds to count down before a game starts or resumes. The
+    /// countdown is skipped if it is 0.
+    pub countdown: u8,
+    /// Number of turns remembered between two steps. Each step takes the
+    /// next one, so turns typed in quick succession aren't lost.
+    pub turn_queue: u8,
+    /// Seed for the random number generator. A random seed is picked for
+    /// every game if none is given.
+    pub seed: Option<u64>,
+}
+
+impl Default for Config {
+    /// The settings of a game started without any options.
+    fn default() -> Self {
+        Config {
+            autopilot: None,
+            arcade: false,
+            grid_width: 20,
+            grid_height: 15,
+            fit_grid: false,
+            no_obstacles: false,
+            level: None,
+            wrap: false,
+            two_player: false,
+            opponents: 0,
+            interval: 175,
+            countdown: 3,
+            turn_queue: 2,
+            seed: None,
+        }
+    }
+}
+
+/// The strategies the computer can use to control the snake. Their names
+/// and implementations are listed in [`crate::strategy::create`].
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Autopilot {
+    /// Chase the food on the shortest path, see [`crate::path::find`].
+    AStar,
+    /// Follow a Hamiltonian cycle, see [`crate::cycle`].
+    Cycle,
+}
+
+impl Autopilot {
+    /// Names of all strategies, as accepted by [`Autopilot::from_str`].
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        STRATEGIES.iter().map(|(_, name, _)| *name)
+    }
+
+    /// Returns the name of the strategy.
+    pub fn name(self) -> &'static str {
+        STRATEGIES
+            .iter()
+            .find(|(autopilot, _, _)| *autopilot == self)
+            .map(|(_, name, _)| *name)
+            .expect("strategy missing from the table")
+    }
+}
+
+impl fmt::Display for Autopilot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Autopilot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        STRATEGIES
+            .iter()
+            .find(|(_, name, _)| *name == s)
+            .map(|(autopilot, _, _)| *autopilot)
+            .ok_or_else(|| format!("unknown autopilot `{}`", s))
+    }
+}
@@ -0,0 +1,564 @@
+//! Autopilot that follows a Hamiltonian cycle.
+//!
+//! A Hamiltonian cycle visits every free tile of the grid exactly once and
+//! returns to where it started. A snake that follows such a cycle can never
+//! run into itself, so it eventually fills the whole grid. To not spend ages
+//! walking around the cycle the snake takes shortcuts to the food while it
+//! is short enough to do that safely.
+//!
+//! The cycle is constructed by splitting the grid into blocks of 2x2 tiles,
+//! building a spanning tree over all blocks without obstacles and walking
+//! around that tree. Free tiles that are left over (at the edge of grids with
+//! an odd size) are spliced into the cycle in pairs where possible. If that
+//! doesn't cover the grid, which is usually the case with obstacles, every
+//! tile is linked to two of its neighbours and the resulting small cycles are
+//! merged into one. Not every grid has a Hamiltonian cycle and this doesn't
+//! find every one that exists, so [`Cycle::build`] can fail.
+
+use std::collections::{BTreeSet, VecDeque};
+
+use crate::game::generate_successors;
+use crate::path::{blocked_tile, get_direction};
+use crate::types::{Direction, Grid, Point, Snake, Tile};
+
+// Shortcuts must keep at least this many tiles between the head and the tail,
+// so the snake can grow without biting its tail.
+const SAFETY_MARGIN: usize = 4;
+
+/// A Hamiltonian cycle over all tiles of a grid that aren't obstacles.
+pub struct Cycle {
+    // The position of every tile on the cycle, indexed as [x][y].
+    index: Vec<Vec<Option<usize>>>,
+    len: usize,
+}
+
+type Edge = (Point, Point);
+
+impl Cycle {
+    /// Builds a cycle through all tiles of the grid that aren't obstacles.
+    /// Returns `None` if no such cycle could be found.
+    pub fn build(grid: &Grid) -> Option<Cycle> {
+        let mut free = 0;
+        let mut even = 0;
+        for (x, row) in grid.iter().enumerate() {
+            for (y, tile) in row.iter().enumerate() {
+                if *tile != Tile::Obstacle {
+                    free += 1;
+                    if (x + y) % 2 == 0 {
+                        even += 1;
+                    }
+                }
+            }
+        }
+
+        // Like a chess board, every step on the grid changes the colour of the tile.
+        // So a cycle can only exist if there are as many tiles of either colour.
+        if free < 4 || even * 2 != free {
+            return None;
+        }
+
+        // Try every alignment of the blocks, as each covers the grid differently.
+        [(1, 1), (0, 0), (1, 0), (0, 1)]
+            .into_iter()
+            .find_map(|offset| build_with_offset(grid, offset, free))
+            .or_else(|| build_from_cycle_cover(grid, free))
+    }
+
+    /// Returns the number of tiles on the cycle.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the cycle has no tiles. Never true for a built cycle.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the direction the snake should take next. Without any free
+    /// tile next to the head `None` is returned.
+    pub fn next_direction(
+        &self,
+        grid: &Grid,
+        snake: &Snake,
+        food: Option<Point>,
+    ) -> Option<Direction> {
+        let head = *snake.front().unwrap();
+        let tail = *snake.back().unwrap();
+
+        // Distance from the head to the point when following the cycle.
+        let h = self.position(head);
+        let distance = |p: Point| (self.position(p) + self.len - h) % self.len;
+
+        let to_tail = distance(tail);
+        let to_food = food.map_or(self.len, distance);
+        let shortcuts = snake.len() < self.len / 2;
+
+        // The snake follows the cycle, so its body is always behind the head
+        // and in front of the tail. It can take any shortcut that doesn't go
+        // past the tail (or the food), and we take the one that skips the
+        // most tiles. If there is none the closest free tile ahead is used.
+        let mut best: Option<(usize, Point)> = None;
+        let mut closest: Option<(usize, Point)> = None;
+        // The cycle never crosses the edge of a wrapping grid and neither do the shortcuts.
+        for p in generate_successors(head, grid, false) {
+            if blocked_tile(grid, p) {
+                continue;
+            }
+            let d = distance(p);
+            if closest.is_none_or(|(c, _)| d < c) {
+                closest = Some((d, p));
+            }
+            let safe = if shortcuts {
+                d <= to_food && d + SAFETY_MARGIN < to_tail
+            } else {
+                d == 1
+            };
+            if safe && best.is_none_or(|(b, _)| d > b) {
+                best = Some((d, p));
+            }
+        }
+
+        let (_, p) = best.or(closest)?;
+        Some(get_direction(head, p, false))
+    }
+
+    fn position(&self, p: Point) -> usize {
+        let (x, y) = p;
+        self.index[x][y].unwrap()
+    }
+}
+
+// Builds a cycle with the 2x2 blocks starting at the given offset.
+fn build_with_offset(grid: &Grid, offset: (usize, usize), free: usize) -> Option<Cycle> {
+    let width = grid.len();
+    let height = grid[0].len();
+    let (offset_x, offset_y) = offset;
+    let blocks_x = (width - offset_x) / 2;
+    let blocks_y = (height - offset_y) / 2;
+
+    // Top left tile of a block.
+    let corner = |(bx, by): Point| (offset_x + bx * 2, offset_y + by * 2);
+    let is_full = |b: Point| {
+        let (x, y) = corner(b);
+        [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)]
+            .iter()
+            .all(|(x, y)| grid[*x][*y] != Tile::Obstacle)
+    };
+
+    // Find the largest connected group of full blocks together with a
+    // spanning tree over it.
+    let mut seen = vec![vec![false; blocks_y]; blocks_x];
+    let mut blocks: Vec<Point> = Vec::new();
+    let mut tree: Vec<Edge> = Vec::new();
+    for bx in 0..blocks_x {
+        for by in 0..blocks_y {
+            if seen[bx][by] || !is_full((bx, by)) {
+                continue;
+            }
+            seen[bx][by] = true;
+            let mut group = vec![(bx, by)];
+            let mut group_tree = Vec::new();
+            let mut stack = vec![(bx, by)];
+            while let Some(b) = stack.pop() {
+                for (nx, ny) in block_neighbours(b, blocks_x, blocks_y) {
+                    if !seen[nx][ny] && is_full((nx, ny)) {
+                        seen[nx][ny] = true;
+                        group.push((nx, ny));
+                        group_tree.push((b, (nx, ny)));
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+            if group.len() > blocks.len() {
+                blocks = group;
+                tree = group_tree;
+            }
+        }
+    }
+    if blocks.is_empty() {
+        return None;
+    }
+
+    // Every block on its own is a small cycle around its four tiles.
+    let mut edges: BTreeSet<Edge> = BTreeSet::new();
+    let mut covered = vec![vec![false; height]; width];
+    for b in blocks.iter() {
+        let (x, y) = corner(*b);
+        let (tl, tr, bl, br) = ((x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1));
+        for (a, b) in [(tl, tr), (tr, br), (bl, br), (tl, bl)] {
+            edges.insert(edge(a, b));
+        }
+        for (x, y) in [tl, tr, bl, br] {
+            covered[x][y] = true;
+        }
+    }
+
+    // Merge the cycles of neighbouring blocks along the spanning tree. The two
+    // facing sides of the blocks are replaced by two edges connecting them.
+    for (a, b) in tree {
+        let (a, b) = if a < b { (a, b) } else { (b, a) };
+        let (ax, ay) = corner(a);
+        let (bx, by) = corner(b);
+        if ay == by {
+            // b is east of a.
+            edges.remove(&edge((ax + 1, ay), (ax + 1, ay + 1)));
+            edges.remove(&edge((bx, by), (bx, by + 1)));
+            edges.insert(edge((ax + 1, ay), (bx, by)));
+            edges.insert(edge((ax + 1, ay + 1), (bx, by + 1)));
+        } else {
+            // b is south of a.
+            edges.remove(&edge((ax, ay + 1), (ax + 1, ay + 1)));
+            edges.remove(&edge((bx, by), (bx + 1, by)));
+            edges.insert(edge((ax, ay + 1), (bx, by)));
+            edges.insert(edge((ax + 1, ay + 1), (bx + 1, by)));
+        }
+    }
+
+    // Splice pairs of uncovered tiles into the cycle: an edge a-b with two
+    // free tiles c-d right next to it becomes a-c-d-b.
+    let uncovered = |covered: &Vec<Vec<bool>>, p: Point| {
+        let (x, y) = p;
+        x < width && y < height && grid[x][y] != Tile::Obstacle && !covered[x][y]
+    };
+    loop {
+        let mut spliced = false;
+        for (a, b) in edges.clone() {
+            let ((ax, ay), (bx, by)) = (a, b);
+            let sides: [(Option<usize>, Option<usize>); 2] = if ay == by {
+                [(Some(ax), ay.checked_sub(1)), (Some(ax), Some(ay + 1))]
+            } else {
+                [(ax.checked_sub(1), Some(ay)), (Some(ax + 1), Some(ay))]
+            };
+            for side in sides {
+                let (Some(cx), Some(cy)) = side else { continue };
+                let c = (cx, cy);
+                let d = (cx + bx - ax, cy + by - ay);
+                if uncovered(&covered, c) && uncovered(&covered, d) {
+                    edges.remove(&(a, b));
+                    edges.insert(edge(a, c));
+                    edges.insert(edge(c, d));
+                    edges.insert(edge(d, b));
+                    covered[c.0][c.1] = true;
+                    covered[d.0][d.1] = true;
+                    spliced = true;
+                    break;
+                }
+            }
+        }
+        if !spliced {
+            break;
+        }
+    }
+
+    from_edges(&edges, width, height, free)
+}
+
+// Builds a cycle for grids the blocks don't work for. First every tile gets
+// linked to exactly two of its neighbours, which splits the grid into many
+// small cycles. Then neighbouring cycles are merged until only one is left.
+fn build_from_cycle_cover(grid: &Grid, free: usize) -> Option<Cycle> {
+    let width = grid.len();
+    let height = grid[0].len();
+    let is_free = |(x, y): Point| grid[x][y] != Tile::Obstacle;
+
+    // Linking tiles is a matching problem: every even tile needs two odd
+    // neighbours and the other way around. We solve it with augmenting paths.
+    let mut links: Vec<Vec<Vec<Point>>> = vec![vec![Vec::with_capacity(2); height]; width];
+    for x in 0..width {
+        for y in 0..height {
+            if (x + y) % 2 != 0 || !is_free((x, y)) {
+                continue;
+            }
+            while links[x][y].len() < 2 {
+                if !augment(grid, &mut links, (x, y)) {
+                    return None;
+                }
+            }
+        }
+    }
+
+    let mut edges: BTreeSet<Edge> = BTreeSet::new();
+    for (x, column) in links.iter().enumerate() {
+        for (y, linked) in column.iter().enumerate() {
+            for p in linked {
+                edges.insert(edge((x, y), *p));
+            }
+        }
+    }
+
+    // Label the small cycles. Merged cycles are tracked with a union find.
+    let mut label = vec![vec![usize::MAX; height]; width];
+    let mut parent: Vec<usize> = Vec::new();
+    for x in 0..width {
+        for y in 0..height {
+            if !is_free((x, y)) || label[x][y] != usize::MAX {
+                continue;
+            }
+            let id = parent.len();
+            parent.push(id);
+            let mut stack = vec![(x, y)];
+            while let Some((px, py)) = stack.pop() {
+                if label[px][py] != usize::MAX {
+                    continue;
+                }
+                label[px][py] = id;
+                stack.extend(links[px][py].iter().copied());
+            }
+        }
+    }
+    fn find(parent: &mut [usize], id: usize) -> usize {
+        let mut root = id;
+        while parent[root] != root {
+            root = parent[root];
+        }
+        parent[id] = root;
+        root
+    }
+
+    // Two cycles running in parallel through a square of four tiles are merged
+    // by replacing their two parallel edges with the other two sides of the square.
+    let mut cycles = parent.len();
+    while cycles > 1 {
+        let mut merged = false;
+        for x in 0..width.saturating_sub(1) {
+            for y in 0..height.saturating_sub(1) {
+                let (tl, tr, bl, br) = ((x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1));
+                for ((a, b), (c, d), (e, f), (g, h)) in [
+                    ((tl, tr), (bl, br), (tl, bl), (tr, br)),
+                    ((tl, bl), (tr, br), (tl, tr), (bl, br)),
+                ] {
+                    if !edges.contains(&(a, b)) || !edges.contains(&(c, d)) {
+                        continue;
+                    }
+                    let first = find(&mut parent, label[a.0][a.1]);
+                    let second = find(&mut parent, label[c.0][c.1]);
+                    if first == second {
+                        continue;
+                    }
+                    edges.remove(&(a, b));
+                    edges.remove(&(c, d));
+                    edges.insert((e, f));
+                    edges.insert((g, h));
+                    parent[second] = first;
+                    cycles -= 1;
+                    merged = true;
+                    break;
+                }
+            }
+        }
+        if !merged {
+            return None;
+        }
+    }
+
+    from_edges(&edges, width, height, free)
+}
+
+// Finds an augmenting path that gives the even tile one more link. Links of
+// odd tiles are moved along the path to make room. Returns false if there is
+// no such path.
+fn augment(grid: &Grid, links: &mut [Vec<Vec<Point>>], start: Point) -> bool {
+    let width = grid.len();
+    let height = grid[0].len();
+    // For odd tiles the even tile they were reached from over a new link,
+    // for even tiles the odd tile they were reached from over an existing link.
+    let mut from: Vec<Vec<Option<Point>>> = vec![vec![None; height]; width];
+    let mut queue = VecDeque::from([start]);
+    from[start.0][start.1] = Some(start);
+
+    while let Some(even) = queue.pop_front() {
+        for odd in generate_successors(even, grid, false) {
+            let (ox, oy) = odd;
+            if grid[ox][oy] == Tile::Obstacle
+                || from[ox][oy].is_some()
+                || links[ox][oy].contains(&even)
+            {
+                continue;
+            }
+            from[ox][oy] = Some(even);
+
+            if links[ox][oy].len() < 2 {
+                // Flip the path: new links become existing ones and the other way around.
+                let mut odd = odd;
+                loop {
+                    let even = from[odd.0][odd.1].unwrap();
+                    links[odd.0][odd.1].push(even);
+                    links[even.0][even.1].push(odd);
+                    if even == start {
+                        return true;
+                    }
+                    let previous = from[even.0][even.1].unwrap();
+                    links[previous.0][previous.1].retain(|p| *p != even);
+                    links[even.0][even.1].retain(|p| *p != previous);
+                    odd = previous;
+                }
+            }
+
+            for next in links[ox][oy].clone() {
+                if from[next.0][next.1].is_none() {
+                    from[next.0][next.1] = Some(odd);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    false
+}
+
+// Walks along the edges and numbers the tiles. Returns `None` unless the
+// edges form a single cycle through all free tiles.
+fn from_edges(edges: &BTreeSet<Edge>, width: usize, height: usize, free: usize) -> Option<Cycle> {
+    let mut links: Vec<Vec<Vec<Point>>> = vec![vec![Vec::with_capacity(2); height]; width];
+    for (a, b) in edges.iter() {
+        links[a.0][a.1].push(*b);
+        links[b.0][b.1].push(*a);
+    }
+    let start = edges.first()?.0;
+    let mut index = vec![vec![None; height]; width];
+    let mut len = 0;
+    let mut previous = start;
+    let mut p = start;
+    loop {
+        let (x, y) = p;
+        if links[x][y].len() != 2 || index[x][y].is_some() {
+            return None;
+        }
+        index[x][y] = Some(len);
+        len += 1;
+        let next = if links[x][y][0] != previous {
+            links[x][y][0]
+        } else {
+            links[x][y][1]
+        };
+        previous = p;
+        p = next;
+        if p == start {
+            break;
+        }
+    }
+
+    if len != free {
+        return None;
+    }
+
+    Some(Cycle { index, len })
+}
+
+fn block_neighbours(b: Point, blocks_x: usize, blocks_y: usize) -> Vec<Point> {
+    let (x, y) = b;
+    let mut neighbours = Vec::with_capacity(4);
+    if x > 0 {
+        neighbours.push((x - 1, y));
+    }
+    if x + 1 < blocks_x {
+        neighbours.push((x + 1, y));
+    }
+    if y > 0 {
+        neighbours.push((x, y - 1));
+    }
+    if y + 1 < blocks_y {
+        neighbours.push((x, y + 1));
+    }
+    neighbours
+}
+
+fn edge(a: Point, b: Point) -> Edge {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{create_grid, Game, StepOutcome};
+
+    // Checks that following the cycle visits every free tile once and that
+    // neighbouring positions on the cycle are neighbours on the grid.
+    fn assert_valid(grid: &Grid, cycle: &Cycle) {
+        let mut points = vec![None; cycle.len()];
+        for (x, row) in grid.iter().enumerate() {
+            for (y, tile) in row.iter().enumerate() {
+                match cycle.index[x][y] {
+                    Some(i) => points[i] = Some((x, y)),
+                    None => assert_eq!(*tile, Tile::Obstacle),
+                }
+            }
+        }
+        for i in 0..cycle.len() {
+            let (ax, ay) = points[i].unwrap();
+            let (bx, by) = points[(i + 1) % cycle.len()].unwrap();
+            assert_eq!(ax.abs_diff(bx) + ay.abs_diff(by), 1);
+        }
+    }
+
+    #[test]
+    fn build_even_grid() {
+        let grid = create_grid(6, 6);
+        let cycle = Cycle::build(&grid).unwrap();
+        assert_eq!(cycle.len(), 16);
+        assert_valid(&grid, &cycle);
+    }
+
+    #[test]
+    fn build_odd_grid() {
+        let grid = create_grid(20, 15);
+        let cycle = Cycle::build(&grid).unwrap();
+        assert_eq!(cycle.len(), 18 * 13);
+        assert_valid(&grid, &cycle);
+    }
+
+    #[test]
+    fn build_with_obstacles() {
+        let mut grid = create_grid(10, 10);
+        grid[3][3] = Tile::Obstacle;
+        grid[3][4] = Tile::Obstacle;
+        grid[6][5] = Tile::Obstacle;
+        grid[6][6] = Tile::Obstacle;
+        let cycle = Cycle::build(&grid).unwrap();
+        assert_eq!(cycle.len(), 60);
+        assert_valid(&grid, &cycle);
+    }
+
+    #[test]
+    fn build_with_scattered_obstacles() {
+        // The blocks leave three free tiles around each obstacle, so this
+        // needs the cycle cover.
+        let mut grid = create_grid(10, 10);
+        grid[3][3] = Tile::Obstacle;
+        grid[6][5] = Tile::Obstacle;
+        let cycle = Cycle::build(&grid).unwrap();
+        assert_eq!(cycle.len(), 62);
+        assert_valid(&grid, &cycle);
+    }
+
+    #[test]
+    fn build_impossible() {
+        // An odd number of free tiles can't form a cycle.
+        let grid = create_grid(5, 5);
+        assert!(Cycle::build(&grid).is_none());
+
+        // Neither can a grid split in two.
+        let mut grid = create_grid(10, 10);
+        grid[5] = vec![Tile::Obstacle; 10];
+        grid[3][3] = Tile::Obstacle;
+        grid[3][4] = Tile::Obstacle;
+        assert!(Cycle::build(&grid).is_none());
+    }
+
+    #[test]
+    fn fills_the_grid() {
+        let mut game = Game::new(12, 11, false, false, 1);
+        let cycle = Cycle::build(game.grid()).unwrap();
+        let mut outcome = StepOutcome::Moved;
+        while !game.is_over() && game.steps() < 100_000 {
+            let direction = cycle.next_direction(game.grid(), game.snake(), game.food());
+            outcome = game.step(direction);
+        }
+        assert_eq!(outcome, StepOutcome::Won);
+        assert_eq!(game.snake().len(), cycle.len());
+    }
+}
@@ -0,0 +1,340 @@
+use crossterm::event::{self, Event, KeyCode};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use snake::level::{self, Level};
+use snake::types::{Direction, Point, Tile};
+
+use crate::output::{self, Renderer, Screen};
+use crate::theme::Theme;
+
+const HELP: &str = "SPACE wall  s start  f food  [ ] width  { } height  c check  w save  q quit";
+
+// Reads the level file to edit. A new level of the given size is created if
+// the file doesn't exist.
+pub fn load(path: &Path, width: u16, height: u16) -> Result<Level, String> {
+    let level = match fs::read_to_string(path) {
+        Ok(content) => level::parse(&content)
+            .map_err(|e| format!("Invalid level file {}: {}", path.display(), e))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Level::new(width.into(), height.into()),
+        Err(e) => return Err(format!("Can't read level file {}: {}", path.display(), e)),
+    };
+
+    let (max_width, max_height) = output::max_grid_size();
+    if level.width() > max_width.into() || level.height() > max_height.into() {
+        return Err(format!(
+            "The level of {}x{} doesn't fit on the screen",
+            level.width(),
+            level.height()
+        ));
+    }
+    Ok(level)
+}
+
+pub fn run(path: &Path, mut level: Level, theme: &Theme) {
+    let mut screen = Screen::new(level.width() as u16, level.height() as u16, theme);
+    let mut cursor = level.snake[0];
+    let mut modified = false;
+    // Quitting with unsaved changes has to be confirmed.
+    let mut confirm_quit = false;
+    // The tiles marked by the connectivity check until the next key is pressed.
+    let mut marked: Vec<Point> = Vec::new();
+    draw_level(&screen, &level);
+    screen.draw_status(String::from(HELP));
+
+    loop {
+        let (width, height) = (level.width() as u16, level.height() as u16);
+        screen.commit();
+        if output::fits(width, height) {
+            screen.show_cursor(cursor);
+        }
+        let key_event = match event::read().unwrap() {
+            Event::Key(key_event) if output::fits(width, height) => key_event,
+            Event::Resize(_, _) => {
+                let status = screen.status();
+                screen = Screen::new(width, height, theme);
+                if output::fits(width, height) {
+                    draw_level(&screen, &level);
+                    screen.draw_status(status);
+                } else {
+                    screen.draw_message("Terminal too small, make it bigger to continue");
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        for p in marked.drain(..) {
+            draw_point(&screen, &level, p);
+        }
+        let (x, y) = cursor;
+        let status = match key_event.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                if !modified || confirm_quit {
+                    break;
+                }
+                confirm_quit = true;
+                screen.draw_status(String::from(
+                    "There are unsaved changes, press q again to quit",
+                ));
+                continue;
+            }
+            KeyCode::Up if y > 0 => {
+                cursor = (x, y - 1);
+                None
+            }
+            KeyCode::Down if y + 1 < level.height() => {
+                cursor = (x, y + 1);
+                None
+            }
+            KeyCode::Left if x > 0 => {
+                cursor = (x - 1, y);
+                None
+            }
+            KeyCode::Right if x + 1 < level.width() => {
+                cursor = (x + 1, y);
+                None
+            }
+            KeyCode::Char(' ') | KeyCode::Char('#') => {
+                if level.snake.contains(&cursor) || level.food == Some(cursor) {
+                    Some(String::from("Move the snake or the food first"))
+                } else {
+                    level.grid[x][y] = match level.grid[x][y] {
+                        Tile::Obstacle => Tile::Free,
+                        _ => Tile::Obstacle,
+                    };
+                    modified = true;
+                    screen.draw_tile(cursor, level.grid[x][y]);
+                    None
+                }
+            }
+            KeyCode::Char('s') => match place_snake(&level, cursor) {
+                Some(snake) => {
+                    let old = std::mem::replace(&mut level.snake, snake.into());
+                    for p in old.into_iter().chain(snake) {
+                        draw_point(&screen, &level, p);
+                    }
+                    modified = true;
+                    None
+                }
+                None => Some(String::from("No room for the snake here")),
+            },
+            KeyCode::Char('f') => {
+                if level.grid[x][y] == Tile::Obstacle || level.snake.contains(&cursor) {
+                    Some(String::from("The food must be placed on a free tile"))
+                } else {
+                    // Pressing f on the food removes it.
+                    let old = level.food;
+                    level.food = if old == Some(cursor) {
+                        None
+                    } else {
+                        Some(cursor)
+                    };
+                    for p in old.into_iter().chain([cursor]) {
+                        draw_point(&screen, &level, p);
+                    }
+                    modified = true;
+                    None
+                }
+            }
+            KeyCode::Char(c @ ('[' | ']' | '{' | '}')) => {
+                let (mut width, mut height) = (level.width(), level.height());
+                match c {
+                    '[' => width -= 1,
+                    ']' => width += 1,
+                    '{' => height -= 1,
+                    _ => height += 1,
+                }
+                let (max_width, max_height) = output::max_grid_size();
+                if width < output::MIN_GRID_WIDTH.into()
+                    || height < output::MIN_GRID_HEIGHT.into()
+                    || width > max_width.into()
+                    || height > max_height.into()
+                {
+                    Some(String::from("The level can't have that size"))
+                } else if let Some(resized) = resize(&level, width, height) {
+                    level = resized;
+                    cursor = (x.min(width - 1), y.min(height - 1));
+                    modified = true;
+                    screen = Screen::new(width as u16, height as u16, theme);
+                    draw_level(&screen, &level);
+                    None
+                } else {
+                    Some(String::from("Move the snake or the food first"))
+                }
+            }
+            KeyCode::Char('c') => {
+                marked = level.unreachable(!level.has_border());
+                for p in marked.iter() {
+                    screen.draw_tile(*p, Tile::Crash);
+                }
+                Some(match marked.len() {
+                    0 => String::from("The snake can reach every free tile"),
+                    n => format!("The snake can't reach {} free tile(s)", n),
+                })
+            }
+            KeyCode::Char('w') => match save(path, &level) {
+                Ok(()) => {
+                    modified = false;
+                    Some(format!("Saved {}", path.display()))
+                }
+                Err(e) => Some(e),
+            },
+            _ => None,
+        };
+        confirm_quit = false;
+        screen.draw_status(status.unwrap_or_else(|| String::from(HELP)));
+    }
+}
+
+// Writes the level to the file, unless the snake can't reach all of it.
+fn save(path: &Path, level: &Level) -> Result<(), String> {
+    if !level.unreachable(!level.has_border()).is_empty() {
+        return Err(String::from(
+            "Can't save, the snake can't reach every free tile (press c to show them)",
+        ));
+    }
+    fs::write(path, level.to_string()).map_err(|e| format!("Can't write {}: {}", path.display(), e))
+}
+
+// Returns the snake with its head at the given point. If the head is already
+// there the snake is turned to the next direction it fits in.
+fn place_snake(level: &Level, head: Point) -> Option<[Point; 2]> {
+    let (x, y) = head;
+    if level.grid[x][y] == Tile::Obstacle || level.food == Some(head) {
+        return None;
+    }
+    let current = Direction::ALL
+        .iter()
+        .position(|d| tail(level, level.snake[0], *d) == Some(level.snake[1]))
+        .unwrap_or(0);
+    // Keep the direction when moving the snake, turn it otherwise.
+    let first = if head == level.snake[0] {
+        current + 1
+    } else {
+        current
+    };
+    (first..first + 4)
+        .map(|i| Direction::ALL[i % 4])
+        .filter_map(|d| tail(level, head, d))
+        .find(|&(x, y)| level.grid[x][y] != Tile::Obstacle && level.food != Some((x, y)))
+        .map(|tail| [head, tail])
+}
+
+// Returns the point behind the head of a snake heading in the given direction.
+fn tail(level: &Level, head: Point, direction: Direction) -> Option<Point> {
+    let (x, y) = head;
+    match direction {
+        Direction::North if y + 1 < level.height() => Some((x, y + 1)),
+        Direction::South if y > 0 => Some((x, y - 1)),
+        Direction::West if x + 1 < level.width() => Some((x + 1, y)),
+        Direction::East if x > 0 => Some((x - 1, y)),
+        _ => None,
+    }
+}
+
+// Changes the size of the level by one column or row. They are added or
+// removed next to the right or bottom edge, so a border stays in place.
+// Returns None if that would remove the snake or the food.
+fn resize(level: &Level, width: usize, height: usize) -> Option<Level> {
+    if height != level.height() {
+        // Rows are the columns of the transposed level.
+        return resize(&transpose(level), height, width).map(|level| transpose(&level));
+    }
+    let mut level = level.clone();
+    let (old_width, old_height) = (level.width(), level.height());
+    // The column next to the right edge.
+    let edge = old_width - 2;
+    let points = level.snake.iter_mut().chain(level.food.iter_mut());
+    if width > old_width {
+        let last = old_height - 1;
+        let mut column = vec![Tile::Free; old_height];
+        column[0] = level.grid[edge][0];
+        column[last] = level.grid[edge][last];
+        level.grid.insert(edge + 1, column);
+        for (x, _) in points {
+            if *x > edge {
+                *x += 1;
+            }
+        }
+    } else if width < old_width {
+        let mut points: Vec<&mut Point> = points.collect();
+        if points.iter().any(|(x, _)| *x == edge) {
+            return None;
+        }
+        level.grid.remove(edge);
+        for (x, _) in points.iter_mut() {
+            if *x > edge {
+                *x -= 1;
+            }
+        }
+    }
+    Some(level)
+}
+
+// Swaps the x and y coordinates of everything in the level.
+fn transpose(level: &Level) -> Level {
+    Level {
+        grid: (0..level.height())
+            .map(|y| (0..level.width()).map(|x| level.grid[x][y]).collect())
+            .collect(),
+        snake: level.snake.iter().map(|&(x, y)| (y, x)).collect(),
+        food: level.food.map(|(x, y)| (y, x)),
+    }
+}
+
+fn draw_level(screen: &impl Renderer, level: &Level) {
+    for x in 0..level.width() {
+        for y in 0..level.height() {
+            draw_point(screen, level, (x, y));
+        }
+    }
+    screen.draw_text_left(format!("{}x{}", level.width(), level.height()));
+}
+
+// Draws the tile at the given point, including the snake and the food.
+fn draw_point(screen: &impl Renderer, level: &Level, p: Point) {
+    let (x, y) = p;
+    if level.snake[0] == p {
+        screen.draw_head(p, 0);
+    } else if level.snake.contains(&p) {
+        screen.draw_snake(p, 0);
+    } else if level.food == Some(p) {
+        screen.draw_tile(p, Tile::Food);
+    } else {
+        screen.draw_tile(p, level.grid[x][y]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resize_keeps_the_border() {
+        let level = Level::new(12, 11);
+        let wider = resize(&level, 13, 11).unwrap();
+        assert_eq!(wider.to_string().lines().next().unwrap(), "#".repeat(13));
+        assert!(wider.has_border());
+        assert_eq!(resize(&wider, 12, 11).unwrap(), level);
+
+        let higher = resize(&level, 12, 12).unwrap();
+        assert!(higher.has_border());
+        assert_eq!(higher.snake, level.snake);
+        assert_eq!(resize(&higher, 12, 11).unwrap(), level);
+    }
+
+    #[test]
+    fn place_and_turn_snake() {
+        let level = Level::new(12, 11);
+        // Moving the snake keeps its direction.
+        assert_eq!(place_snake(&level, (3, 3)), Some([(3, 3), (2, 3)]));
+        // Placing it again turns it.
+        let head = level.snake[0];
+        let (x, y) = head;
+        assert_eq!(place_snake(&level, head), Some([head, (x, y + 1)]));
+        // The tail doesn't fit on the border.
+        assert_eq!(place_snake(&level, (1, 1)), Some([(1, 1), (1, 2)]));
+        assert_eq!(place_snake(&level, (0, 0)), None);
+    }
+}
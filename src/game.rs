@@ -0,0 +1,792 @@
+//! Game rules and grid construction.
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::collections::VecDeque;
+
+use crate::level::Level;
+use crate::path::get_direction;
+use crate::types::{Direction, Grid, Point, Snake, Tile};
+
+/// The result of advancing the game by a single step.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StepOutcome {
+    /// The snake moved onto a free tile.
+    Moved,
+    /// The snake ate the food and grew by one tile.
+    Ate,
+    /// The snake ate the last food and now fills the whole grid. The game is over.
+    Won,
+    /// The snake crashed at the given point and the game is over.
+    Crashed(Point),
+}
+
+/// The state of a single game of snake.
+///
+/// It contains all the game rules but doesn't know anything about the
+/// terminal, so it can be driven by the UI loop as well as by tests or
+/// simulations. A game has a snake for every player, most games have a
+/// single player.
+pub struct Game {
+    grid: Grid,
+    // The snake and the direction of every player.
+    snakes: Vec<Snake>,
+    directions: Vec<Direction>,
+    food: Option<Point>,
+    steps: u32,
+    over: bool,
+    seed: u64,
+    wrap: bool,
+    rng: StdRng,
+}
+
+impl Game {
+    /// Creates a new game on a grid of the given size with a border of
+    /// obstacles. The snake and the food are placed at random and, if
+    /// requested, some random obstacles are scattered over the grid.
+    ///
+    /// With `wrap` the grid has no border. Instead the snake leaves the grid
+    /// on one edge and re-enters it on the opposite side.
+    ///
+    /// All randomness of the game comes from a single RNG seeded with
+    /// `seed`, so the same seed and the same inputs replay the same game.
+    ///
+    /// # Panics
+    ///
+    /// If the grid has no room for the snake and the food.
+    pub fn new(width: usize, height: usize, obstacles: bool, wrap: bool, seed: u64) -> Self {
+        Game::with_players(width, height, obstacles, wrap, 1, seed)
+            .expect("grid too small for the snake")
+    }
+
+    /// Like [`Game::new`], but places a snake for each of the given number
+    /// of players. Returns `None` if the grid has no room for the snakes and
+    /// the food.
+    pub fn with_players(
+        width: usize,
+        height: usize,
+        obstacles: bool,
+        wrap: bool,
+        players: usize,
+        seed: u64,
+    ) -> Option<Self> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut grid = if wrap {
+            vec![vec![Tile::Free; height]; width]
+        } else {
+            create_grid(width, height)
+        };
+        let snakes = (0..players)
+            .map(|_| spawn_snake(&mut grid, &mut rng))
+            .collect::<Option<_>>()?;
+        let food = spawn_food(&mut grid, &mut rng)?;
+        if obstacles {
+            spawn_obstacles(&mut grid, (width * height / 25) as u16, wrap, &mut rng);
+        }
+
+        Some(Game::with_rng(grid, snakes, food, wrap, seed, rng))
+    }
+
+    /// Creates a game on the grid of a level. The snake of the first player
+    /// starts where the level says, the others are placed at random. So is
+    /// the food unless the level places it. Returns `None` if the level has
+    /// no room for the other snakes or the food.
+    pub fn from_level(level: &Level, wrap: bool, players: usize, seed: u64) -> Option<Self> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut grid = level.grid.clone();
+        for &(x, y) in level.snake.iter() {
+            grid[x][y] = Tile::Snake;
+        }
+        // The food of the level is placed first, so the snakes avoid it.
+        if let Some((x, y)) = level.food {
+            grid[x][y] = Tile::Food;
+        }
+        let mut snakes = vec![level.snake.clone()];
+        for _ in 1..players {
+            snakes.push(spawn_snake(&mut grid, &mut rng)?);
+        }
+        let food = match level.food {
+            Some(food) => food,
+            None => spawn_food(&mut grid, &mut rng)?,
+        };
+
+        Some(Game::with_rng(grid, snakes, food, wrap, seed, rng))
+    }
+
+    /// Creates a game from an existing grid. The snake (head first) and the
+    /// food must already be placed on the grid and the snake must be at
+    /// least two tiles long. The seed is used to spawn new food.
+    pub fn from_parts(grid: Grid, snake: Snake, food: Point, wrap: bool, seed: u64) -> Self {
+        Game::with_rng(
+            grid,
+            vec![snake],
+            food,
+            wrap,
+            seed,
+            StdRng::seed_from_u64(seed),
+        )
+    }
+
+    fn with_rng(
+        grid: Grid,
+        snakes: Vec<Snake>,
+        food: Point,
+        wrap: bool,
+        seed: u64,
+        rng: StdRng,
+    ) -> Self {
+        // Start moving in the direction the snakes are facing.
+        let directions = snakes.iter().map(|s| snake_direction(s, wrap)).collect();
+
+        Game {
+            grid,
+            snakes,
+            directions,
+            food: Some(food),
+            steps: 0,
+            over: false,
+            seed,
+            wrap,
+            rng,
+        }
+    }
+
+    /// Advances the snake by one tile. If a direction is given the snake
+    /// turns before moving, unless that would reverse it onto itself.
+    ///
+    /// In a game with several players this steers the first snake, the
+    /// others keep their direction. See [`Game::step_all`].
+    pub fn step(&mut self, direction: Option<Direction>) -> StepOutcome {
+        self.step_all(&[direction])[0]
+    }
+
+    /// Advances all snakes by one tile at the same time and returns what
+    /// happened to each of them. The directions are given in the order of
+    /// the players, snakes without a direction keep moving straight on.
+    ///
+    /// A snake crashes when it runs into an obstacle or into any snake, or
+    /// when two snakes move onto the same tile. The game is over as soon as
+    /// one of the snakes crashed.
+    pub fn step_all(&mut self, directions: &[Option<Direction>]) -> Vec<StepOutcome> {
+        for (i, direction) in directions.iter().enumerate() {
+            if let Some(d) = *direction {
+                // The snake can't reverse direction. So if the new direction is the opposite
+                // of the current one we discard it.
+                if d != snake_direction(&self.snakes[i], self.wrap).opposite() {
+                    self.directions[i] = d;
+                }
+            }
+        }
+
+        // Return points in front of the snakes in their directions.
+        let heads: Vec<Point> = self
+            .snakes
+            .iter()
+            .zip(self.directions.iter())
+            .map(|(snake, d)| next_point(*snake.front().unwrap(), *d, &self.grid, self.wrap))
+            .collect();
+
+        self.steps += 1;
+
+        // Check the tiles in the grid before anything moves, so the order of
+        // the players doesn't matter.
+        let mut outcomes: Vec<StepOutcome> = heads
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| {
+                let (x, y) = p;
+                let head_on = heads.iter().enumerate().any(|(j, &q)| i != j && p == q);
+                match self.grid[x][y] {
+                    // The snake crashed.
+                    Tile::Obstacle | Tile::Snake | Tile::Crash => StepOutcome::Crashed(p),
+                    // Two snakes ran into each other.
+                    _ if head_on => StepOutcome::Crashed(p),
+                    Tile::Food => StepOutcome::Ate,
+                    Tile::Free => StepOutcome::Moved,
+                }
+            })
+            .collect();
+
+        for (i, outcome) in outcomes.iter().enumerate() {
+            let (x, y) = heads[i];
+            let snake = &mut self.snakes[i];
+            match outcome {
+                StepOutcome::Ate => {
+                    snake.push_front(heads[i]);
+                    self.grid[x][y] = Tile::Snake;
+                }
+                // If the tile is free we pop the tail of the snake to make it look like it is moving.
+                StepOutcome::Moved => {
+                    snake.push_front(heads[i]);
+                    self.grid[x][y] = Tile::Snake;
+                    let (tail_x, tail_y) = snake.pop_back().unwrap();
+                    self.grid[tail_x][tail_y] = Tile::Free;
+                }
+                _ => {}
+            }
+        }
+
+        // The crashed snakes stay where they are and end the game. This comes
+        // last as a snake may have crashed into the tail of another one.
+        for outcome in outcomes.iter() {
+            if let StepOutcome::Crashed((x, y)) = *outcome {
+                self.grid[x][y] = Tile::Crash;
+                self.over = true;
+            }
+        }
+
+        // A snake ate - spawn new food. If there is no room left for it
+        // the snakes fill the whole grid.
+        if let Some(i) = outcomes.iter().position(|o| *o == StepOutcome::Ate) {
+            self.food = spawn_food(&mut self.grid, &mut self.rng);
+            if self.food.is_none() {
+                self.over = true;
+                outcomes[i] = StepOutcome::Won;
+            }
+        }
+
+        outcomes
+    }
+
+    /// Returns the grid with all tiles.
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    /// Returns the points of the snake, head first. In a game with several
+    /// players this is the snake of the first player.
+    pub fn snake(&self) -> &Snake {
+        &self.snakes[0]
+    }
+
+    /// Returns the head of the snake.
+    pub fn head(&self) -> &Point {
+        self.snake().front().unwrap()
+    }
+
+    /// Returns the direction the snake moved in with the last step, or will
+    /// move in with the first one.
+    pub fn direction(&self) -> Direction {
+        self.directions[0]
+    }
+
+    /// Returns the directions the snakes of all players moved in with the
+    /// last step.
+    pub fn directions(&self) -> &[Direction] {
+        &self.directions
+    }
+
+    /// Returns the snakes of all players.
+    pub fn snakes(&self) -> &[Snake] {
+        &self.snakes
+    }
+
+    /// Returns the number of players.
+    pub fn players(&self) -> usize {
+        self.snakes.len()
+    }
+
+    /// Returns the position of the food, or `None` once the snake fills the grid.
+    pub fn food(&self) -> Option<Point> {
+        self.food
+    }
+
+    /// Returns the number of steps taken so far.
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    /// Returns the seed the game was created with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns true if the edges of the grid wrap around.
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
+
+    /// Returns true once a snake crashed or the snakes filled the grid.
+    pub fn is_over(&self) -> bool {
+        self.over
+    }
+}
+
+/// Returns the next point in the given direction. With `wrap` a point on
+/// the edge of the grid continues on the opposite edge, otherwise the grid
+/// must have a border the point can't move past.
+pub fn next_point(p: Point, direction: Direction, grid: &Grid, wrap: bool) -> Point {
+    let (x, y) = p;
+    if wrap {
+        let width = grid.len();
+        let height = grid[0].len();
+        return match direction {
+            Direction::North => (x, (y + height - 1) % height),
+            Direction::South => (x, (y + 1) % height),
+            Direction::West => ((x + width - 1) % width, y),
+            Direction::East => ((x + 1) % width, y),
+        };
+    }
+    match direction {
+        Direction::North => (x, y - 1),
+        Direction::South => (x, y + 1),
+        Direction::West => (x - 1, y),
+        Direction::East => (x + 1, y),
+    }
+}
+
+/// Generates all valid successors of a point. With `wrap` the points on the
+/// opposite edge are successors of a point on the edge.
+///
+/// ```text
+///           N
+///           |
+///      W--Point--E
+///           |
+///           S
+/// ```
+pub fn generate_successors(p: Point, grid: &Grid, wrap: bool) -> Vec<Point> {
+    let mut successors: Vec<Point> = Vec::with_capacity(4);
+    let (x, y) = p;
+
+    if wrap || x > 0 {
+        successors.push(next_point(p, Direction::West, grid, wrap));
+    }
+    if wrap || x + 1 < grid.len() {
+        successors.push(next_point(p, Direction::East, grid, wrap));
+    }
+    if wrap || y + 1 < grid[0].len() {
+        successors.push(next_point(p, Direction::South, grid, wrap));
+    }
+    if wrap || y > 0 {
+        successors.push(next_point(p, Direction::North, grid, wrap))
+    }
+
+    successors
+}
+
+/// Creates an empty grid with a border of obstacles.
+pub fn create_grid(width: usize, height: usize) -> Grid {
+    let mut grid = vec![vec![Tile::Free; height]; width];
+    for (x, row) in grid.iter_mut().enumerate() {
+        for (y, tile) in row.iter_mut().enumerate() {
+            if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                *tile = Tile::Obstacle;
+            };
+        }
+    }
+    grid
+}
+
+/// Places a snake of length two at a random point on the grid. Points far
+/// from the edge are preferred, closer ones are only used if there is no
+/// room further in. Returns `None` if there is no room for the snake at all.
+pub fn spawn_snake<R: Rng>(grid: &mut Grid, rng: &mut R) -> Option<Snake> {
+    // The snake is placed away from the edge, so it doesn't matter if the grid wraps.
+    let free_directions = |grid: &Grid, p: Point| -> Vec<Direction> {
+        Direction::ALL
+            .into_iter()
+            .filter(|d| {
+                let (x, y) = next_point(p, *d, grid, false);
+                grid[x][y] == Tile::Free
+            })
+            .collect()
+    };
+    let (x, y) = (1..=5).rev().find_map(|distance| {
+        random_point(grid, distance, rng, |p| {
+            !free_directions(grid, p).is_empty()
+        })
+    })?;
+    grid[x][y] = Tile::Snake;
+    let mut snake = VecDeque::with_capacity(10);
+    snake.push_front((x, y));
+    let direction = *free_directions(grid, (x, y)).choose(rng).unwrap();
+    let (x, y) = next_point((x, y), direction, grid, false);
+    grid[x][y] = Tile::Snake;
+    snake.push_front((x, y));
+    Some(snake)
+}
+
+/// Places the food at a random free point on the grid. Points away from the
+/// edge are preferred. Returns `None` if there is no free point left.
+pub fn spawn_food<R: Rng>(grid: &mut Grid, rng: &mut R) -> Option<Point> {
+    let (x, y) = random_empty_point(grid, 2, rng).or_else(|| random_empty_point(grid, 0, rng))?;
+    grid[x][y] = Tile::Food;
+    Some((x, y))
+}
+
+/// Places `count` obstacles at random free points on the grid, avoiding
+/// dead ends.
+pub fn spawn_obstacles<R: Rng>(grid: &mut Grid, count: u16, wrap: bool, rng: &mut R) {
+    for _ in 0..=count {
+        // avoid creating dead ends
+        'outer: loop {
+            let p = random_empty_point(grid, 0, rng).unwrap();
+            let (x, y) = p;
+            grid[x][y] = Tile::Obstacle;
+            for (a, b) in generate_successors(p, grid, wrap) {
+                if grid[a][b] == Tile::Free && is_in_dead_end(grid, (a, b), wrap) {
+                    grid[x][y] = Tile::Free;
+                    continue 'outer;
+                }
+            }
+            break 'outer;
+        }
+    }
+}
+
+// Returns a random empty point on the grid. The distance parameter specifies
+// the minimum distance from the edge of the grid, the tiles on the edge have
+// a distance of 0.
+fn random_empty_point<R: Rng>(grid: &Grid, distance: usize, rng: &mut R) -> Option<Point> {
+    random_point(grid, distance, rng, |_| true)
+}
+
+// Like random_empty_point(), but only returns points the filter accepts.
+fn random_point<R: Rng>(
+    grid: &Grid,
+    distance: usize,
+    rng: &mut R,
+    filter: impl Fn(Point) -> bool,
+) -> Option<Point> {
+    if grid.len() <= distance * 2 || grid[0].len() <= distance * 2 {
+        return None;
+    }
+    let min_x = distance;
+    let max_x = grid.len() - distance - 1;
+    let min_y = distance;
+    let max_y = grid[0].len() - distance - 1;
+
+    let mut points = Vec::with_capacity(grid.len() * grid[0].len());
+    for (x, row) in grid.iter().enumerate() {
+        for (y, tile) in row.iter().enumerate() {
+            if x >= min_x
+                && x <= max_x
+                && y >= min_y
+                && y <= max_y
+                && *tile == Tile::Free
+                && filter((x, y))
+            {
+                points.push((x, y))
+            }
+        }
+    }
+
+    points.choose(rng).copied()
+}
+
+// Checks if point is in this shape: #p#
+//                                    #
+fn is_in_dead_end(grid: &Grid, p: Point, wrap: bool) -> bool {
+    let mut free = 0;
+    for (x, y) in generate_successors(p, grid, wrap) {
+        if grid[x][y] == Tile::Free {
+            free += 1;
+        }
+    }
+
+    free < 2
+}
+
+fn snake_direction(snake: &Snake, wrap: bool) -> Direction {
+    let head = *snake.front().unwrap();
+    let neck = *snake.get(1).unwrap();
+    get_direction(neck, head, wrap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Builds a game on a bordered 8x5 grid with a snake of length 2 at (2,2)
+    // heading east and the food right in front of it.
+    fn test_game() -> Game {
+        let mut grid = create_grid(8, 5);
+        grid[1][2] = Tile::Snake;
+        grid[2][2] = Tile::Snake;
+        grid[3][2] = Tile::Food;
+        Game {
+            grid,
+            snakes: vec![VecDeque::from([(2, 2), (1, 2)])],
+            directions: vec![Direction::East],
+            food: Some((3, 2)),
+            steps: 0,
+            over: false,
+            seed: 0,
+            wrap: false,
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let mut a = Game::new(20, 15, true, false, 42);
+        let mut b = Game::new(20, 15, true, false, 42);
+        for _ in 0..5 {
+            a.step(None);
+            b.step(None);
+        }
+        assert_eq!(a.grid(), b.grid());
+        assert_eq!(a.snake(), b.snake());
+        assert_eq!(a.food(), b.food());
+    }
+
+    #[test]
+    fn step_eats_food() {
+        let mut game = test_game();
+        assert_eq!(game.step(None), StepOutcome::Ate);
+        assert_eq!(game.snake().len(), 3);
+        assert_eq!(*game.head(), (3, 2));
+        let (x, y) = game.food().unwrap();
+        assert_ne!((x, y), (3, 2));
+        assert_eq!(game.grid()[x][y], Tile::Food);
+        assert_eq!(game.steps(), 1);
+    }
+
+    #[test]
+    fn step_moves_snake() {
+        let mut game = test_game();
+        assert_eq!(game.step(Some(Direction::North)), StepOutcome::Moved);
+        assert_eq!(game.snake(), &VecDeque::from([(2, 1), (2, 2)]));
+        assert_eq!(game.grid()[1][2], Tile::Free);
+    }
+
+    #[test]
+    fn step_ignores_reverse_direction() {
+        let mut game = test_game();
+        assert_eq!(game.step(Some(Direction::West)), StepOutcome::Ate);
+        assert_eq!(*game.head(), (3, 2));
+    }
+
+    #[test]
+    fn step_crashes_into_wall() {
+        let mut game = test_game();
+        game.step(Some(Direction::North));
+        assert_eq!(game.step(None), StepOutcome::Crashed((2, 0)));
+        assert!(game.is_over());
+        assert_eq!(game.grid()[2][0], Tile::Crash);
+    }
+
+    // Builds a game for two players on a bordered 9x5 grid. The snakes are
+    // in the middle row heading towards each other with one free tile between
+    // them. The food is out of the way.
+    fn two_player_game() -> Game {
+        let mut grid = create_grid(9, 5);
+        for x in [1, 2, 6, 7] {
+            grid[x][2] = Tile::Snake;
+        }
+        grid[1][1] = Tile::Food;
+        Game {
+            grid,
+            snakes: vec![
+                VecDeque::from([(2, 2), (1, 2)]),
+                VecDeque::from([(6, 2), (7, 2)]),
+            ],
+            directions: vec![Direction::East, Direction::West],
+            food: Some((1, 1)),
+            steps: 0,
+            over: false,
+            seed: 0,
+            wrap: false,
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+
+    #[test]
+    fn step_all_moves_every_snake() {
+        let mut game = two_player_game();
+        assert_eq!(
+            game.step_all(&[None, Some(Direction::North)]),
+            vec![StepOutcome::Moved, StepOutcome::Moved]
+        );
+        assert_eq!(game.snakes()[0], VecDeque::from([(3, 2), (2, 2)]));
+        assert_eq!(game.snakes()[1], VecDeque::from([(6, 1), (6, 2)]));
+        assert!(!game.is_over());
+    }
+
+    #[test]
+    fn step_all_crashes_head_to_head() {
+        let mut game = two_player_game();
+        game.step_all(&[None, None]);
+        assert_eq!(
+            game.step_all(&[None, None]),
+            vec![StepOutcome::Crashed((4, 2)), StepOutcome::Crashed((4, 2))]
+        );
+        assert!(game.is_over());
+        assert_eq!(game.grid()[4][2], Tile::Crash);
+    }
+
+    #[test]
+    fn step_all_crashes_into_other_snake() {
+        let mut game = two_player_game();
+        game.step_all(&[Some(Direction::North), Some(Direction::North)]);
+        game.step_all(&[Some(Direction::East), Some(Direction::West)]);
+        game.step_all(&[None, Some(Direction::South)]);
+        // The second snake now lies in the column right in front of the first
+        // one. Its tail moves on, but too late for the first snake.
+        assert_eq!(
+            game.step_all(&[None, None]),
+            vec![StepOutcome::Crashed((5, 1)), StepOutcome::Moved]
+        );
+        assert_eq!(game.grid()[5][1], Tile::Crash);
+        assert!(game.is_over());
+    }
+
+    #[test]
+    fn step_wraps_around_edges() {
+        let mut grid = vec![vec![Tile::Free; 5]; 5];
+        grid[0][2] = Tile::Snake;
+        grid[1][2] = Tile::Snake;
+        grid[3][3] = Tile::Food;
+        let snake = VecDeque::from([(0, 2), (1, 2)]);
+        let mut game = Game::from_parts(grid, snake, (3, 3), true, 0);
+        assert_eq!(game.step(None), StepOutcome::Moved);
+        assert_eq!(*game.head(), (4, 2));
+        assert_eq!(game.step(Some(Direction::North)), StepOutcome::Moved);
+        assert_eq!(game.step(None), StepOutcome::Moved);
+        assert_eq!(game.step(None), StepOutcome::Moved);
+        assert_eq!(*game.head(), (4, 4));
+        // Reversing is still detected across the edge.
+        assert_eq!(game.step(Some(Direction::South)), StepOutcome::Moved);
+        assert_eq!(*game.head(), (4, 3));
+    }
+
+    #[test]
+    fn from_level_places_snakes_and_food() {
+        let mut map = String::from("############\n#.sS.......#\n#....F.....#\n");
+        map.push_str(&"#..........#\n".repeat(7));
+        map.push_str("############");
+        let level = crate::level::parse(&map).unwrap();
+        let mut game = Game::from_level(&level, false, 2, 1).unwrap();
+        assert_eq!(*game.head(), (3, 1));
+        assert_eq!(game.direction(), Direction::East);
+        assert_eq!(game.food(), Some((5, 2)));
+        assert_eq!(game.snakes()[1].len(), 2);
+        assert_eq!(game.step(Some(Direction::South)), StepOutcome::Moved);
+    }
+
+    #[test]
+    fn from_level_keeps_food_free() {
+        let mut map = String::from("####################\n#sS................#\n");
+        map.push_str(&"#..................#\n".repeat(5));
+        map.push_str("#.........F........#\n");
+        map.push_str(&"#..................#\n".repeat(6));
+        map.push_str("####################");
+        let level = crate::level::parse(&map).unwrap();
+        for seed in 0..500 {
+            let game = Game::from_level(&level, false, 4, seed).unwrap();
+            assert_eq!(game.food(), Some((10, 7)));
+            assert_eq!(game.grid()[10][7], Tile::Food);
+            assert!(game.snakes().iter().all(|snake| !snake.contains(&(10, 7))));
+        }
+    }
+
+    #[test]
+    fn spawn_two_players_on_small_grid() {
+        // The smallest grid the game allows.
+        for seed in 0..100 {
+            let game = Game::with_players(12, 11, true, false, 2, seed).unwrap();
+            for snake in game.snakes() {
+                assert_eq!(snake.len(), 2);
+                for &(x, y) in snake.iter() {
+                    assert_eq!(game.grid()[x][y], Tile::Snake);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn spawn_four_snakes_on_small_grid() {
+        for seed in 0..100 {
+            let game = Game::with_players(12, 11, true, false, 4, seed).unwrap();
+            assert_eq!(game.players(), 4);
+            assert!(game.snakes().iter().all(|snake| snake.len() == 2));
+        }
+        // Four tiles inside the border only fit one snake and the food.
+        assert!(Game::with_players(4, 4, false, false, 1, 1).is_some());
+        assert!(Game::with_players(4, 4, false, false, 3, 1).is_none());
+    }
+
+    #[test]
+    fn from_level_without_room_for_snakes() {
+        let mut map = String::from("############\n#sS........#\n");
+        map.push_str(&"############\n".repeat(8));
+        map.push_str("############");
+        let level = crate::level::parse(&map).unwrap();
+        assert!(Game::from_level(&level, false, 1, 1).is_some());
+        // The row is long enough for a second snake, but not for a fifth.
+        assert!(Game::from_level(&level, false, 2, 1).is_some());
+        assert!(Game::from_level(&level, false, 5, 1).is_none());
+    }
+
+    #[test]
+    fn step_wins_on_full_grid() {
+        let mut grid = create_grid(5, 4);
+        grid[1][1] = Tile::Snake;
+        grid[2][1] = Tile::Snake;
+        grid[3][1] = Tile::Snake;
+        grid[3][2] = Tile::Snake;
+        grid[2][2] = Tile::Snake;
+        grid[1][2] = Tile::Food;
+        let snake = VecDeque::from([(2, 2), (3, 2), (3, 1), (2, 1), (1, 1)]);
+        let mut game = Game::from_parts(grid, snake, (1, 2), false, 0);
+        assert_eq!(game.step(None), StepOutcome::Won);
+        assert!(game.is_over());
+        assert_eq!(game.food(), None);
+    }
+
+    #[test]
+    fn is_dead_end_empty() {
+        let grid = vec![vec![Tile::Free; 3]; 3];
+        assert!(!is_in_dead_end(&grid, (0, 0), false));
+        assert!(!is_in_dead_end(&grid, (1, 1), false));
+    }
+
+    #[test]
+    fn is_dead_end_with_obstacle() {
+        let mut grid = vec![vec![Tile::Free; 3]; 3];
+
+        // obstacle
+        grid[0][0] = Tile::Obstacle;
+        grid[2][0] = Tile::Obstacle;
+
+        // true
+        assert!(is_in_dead_end(&grid, (1, 0), false));
+
+        // false
+        assert!(!is_in_dead_end(&grid, (0, 1), false));
+        assert!(!is_in_dead_end(&grid, (0, 2), false));
+        assert!(!is_in_dead_end(&grid, (1, 1), false));
+        assert!(!is_in_dead_end(&grid, (1, 2), false));
+        assert!(!is_in_dead_end(&grid, (2, 1), false));
+        assert!(!is_in_dead_end(&grid, (2, 2), false));
+    }
+
+    #[test]
+    fn is_dead_end_with_obstacle_and_border() {
+        let mut grid = vec![vec![Tile::Free; 4]; 4];
+
+        // border
+        grid[0][0] = Tile::Obstacle;
+        grid[1][0] = Tile::Obstacle;
+        grid[2][0] = Tile::Obstacle;
+        grid[3][0] = Tile::Obstacle;
+        grid[0][3] = Tile::Obstacle;
+        grid[1][3] = Tile::Obstacle;
+        grid[2][3] = Tile::Obstacle;
+        grid[3][3] = Tile::Obstacle;
+        grid[0][1] = Tile::Obstacle;
+        grid[0][2] = Tile::Obstacle;
+        grid[3][1] = Tile::Obstacle;
+        grid[3][2] = Tile::Obstacle;
+        // obstacle
+        grid[1][1] = Tile::Obstacle;
+        grid[3][1] = Tile::Obstacle;
+
+        // true
+        assert!(is_in_dead_end(&grid, (2, 1), false));
+
+        // false
+        assert!(!is_in_dead_end(&grid, (2, 2), false));
+    }
+}
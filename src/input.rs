@@ -1,29 +1,38 @@
-use crossterm::event;
-use crossterm::event::{Event, KeyCode};
+use crossterm::event::{self, Event};
 use std::sync::atomic::{self, AtomicU16};
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
-use crate::types::Direction;
+use snake::types::Direction;
+
+use crate::keymap::Keymap;
 
 #[repr(u8)]
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Input {
     Pause,
     Exit,
     Step,
-    ChangeDirection(Direction),
+    // The player (counted from 0) and the direction the snake should turn to.
+    ChangeDirection(usize, Direction),
     IncreaseSpeed,
     DecreaseSpeed,
+    // Save the replay of the game that just ended.
+    SaveReplay,
+    Digit(u8),
+    Enter,
+    // The terminal was resized.
+    Resize,
     Unknown,
 }
 
-// Spawns a thread that reads input events and sends them to the channel.
-pub fn handle(tx: Sender<Input>) {
+// Spawns a thread that reads input events, maps them with the keymap and
+// sends them to the channel.
+pub fn handle(tx: Sender<Input>, keymap: Keymap) {
     thread::spawn(move || loop {
-        tx.send(read()).unwrap();
+        tx.send(read(&keymap)).unwrap();
     });
 }
 
@@ -38,20 +47,10 @@ pub fn send_ticks(tx: Sender<Input>, interval: Arc<AtomicU16>) {
 }
 
 // Waits for an UI event and returns the corresponding Input enum.
-fn read() -> Input {
-    let e = event::read().unwrap();
-    match e {
-        Event::Key(key_event) => match key_event.code {
-            KeyCode::Esc | KeyCode::Char('q') => Input::Exit,
-            KeyCode::Up => Input::ChangeDirection(Direction::North),
-            KeyCode::Down => Input::ChangeDirection(Direction::South),
-            KeyCode::Right => Input::ChangeDirection(Direction::East),
-            KeyCode::Left => Input::ChangeDirection(Direction::West),
-            KeyCode::Char(' ') => Input::Pause,
-            KeyCode::Char('+') => Input::IncreaseSpeed,
-            KeyCode::Char('-') => Input::DecreaseSpeed,
-            _ => Input::Unknown,
-        },
+fn read(keymap: &Keymap) -> Input {
+    match event::read().unwrap() {
+        Event::Key(key_event) => keymap.get(&key_event),
+        Event::Resize(_, _) => Input::Resize,
         _ => Input::Unknown,
     }
 }
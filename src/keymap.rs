@@ -0,0 +1,308 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use snake::types::Direction;
+
+use crate::input::Input;
+
+// The actions that can be bound to keys, by the name used in the config file.
+const ACTIONS: [(&str, Input); 13] = [
+    ("up", Input::ChangeDirection(0, Direction::North)),
+    ("down", Input::ChangeDirection(0, Direction::South)),
+    ("left", Input::ChangeDirection(0, Direction::West)),
+    ("right", Input::ChangeDirection(0, Direction::East)),
+    ("p2-up", Input::ChangeDirection(1, Direction::North)),
+    ("p2-down", Input::ChangeDirection(1, Direction::South)),
+    ("p2-left", Input::ChangeDirection(1, Direction::West)),
+    ("p2-right", Input::ChangeDirection(1, Direction::East)),
+    ("pause", Input::Pause),
+    ("quit", Input::Exit),
+    ("faster", Input::IncreaseSpeed),
+    ("slower", Input::DecreaseSpeed),
+    ("save-replay", Input::SaveReplay),
+];
+
+// Names of the built-in keymaps, the first one is the default.
+pub const PRESETS: [&str; 3] = ["arrows", "vim", "wasd"];
+
+// A key together with the modifiers pressed with it.
+type Key = (KeyCode, KeyModifiers);
+
+// Maps keys to the inputs of the game.
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    bindings: Vec<(Key, Input)>,
+}
+
+impl Keymap {
+    // Returns the built-in keymap with the given name. All of them steer
+    // the second player with the keys the first one doesn't use.
+    pub fn preset(name: &str) -> Option<Self> {
+        let (p1, p2) = match name {
+            "arrows" => (["up", "down", "left", "right"], ["w", "s", "a", "d"]),
+            "vim" => (["k", "j", "h", "l"], ["w", "s", "a", "d"]),
+            "wasd" => (["w", "s", "a", "d"], ["up", "down", "left", "right"]),
+            _ => return None,
+        };
+        let mut keymap = Keymap {
+            bindings: Vec::new(),
+        };
+        for (i, key) in p1.iter().enumerate() {
+            keymap.bind(key, ACTIONS[i].1).unwrap();
+        }
+        // With vim keys the arrows work as well.
+        if name == "vim" {
+            for (i, key) in ["up", "down", "left", "right"].iter().enumerate() {
+                keymap.bind(key, ACTIONS[i].1).unwrap();
+            }
+        }
+        for (i, key) in p2.iter().enumerate() {
+            keymap.bind(key, ACTIONS[i + 4].1).unwrap();
+        }
+        for (key, input) in [
+            ("space", Input::Pause),
+            ("q", Input::Exit),
+            ("esc", Input::Exit),
+            ("ctrl-c", Input::Exit),
+            ("+", Input::IncreaseSpeed),
+            ("-", Input::DecreaseSpeed),
+            ("r", Input::SaveReplay),
+        ] {
+            keymap.bind(key, input).unwrap();
+        }
+        Some(keymap)
+    }
+
+    // Binds the keys to the action with the given name instead of the keys
+    // it had before.
+    pub fn rebind(&mut self, action: &str, keys: &[String]) -> Result<(), String> {
+        let (_, input) = ACTIONS
+            .iter()
+            .find(|(name, _)| *name == action)
+            .ok_or_else(|| format!("unknown action `{}`", action))?;
+        self.bindings.retain(|(_, i)| i != input);
+        for key in keys {
+            self.bind(key, *input)?;
+        }
+        Ok(())
+    }
+
+    // Binds the key to the input, replacing its previous binding.
+    fn bind(&mut self, key: &str, input: Input) -> Result<(), String> {
+        let key = parse_key(key)?;
+        self.bindings.retain(|(k, _)| *k != key);
+        self.bindings.push((key, input));
+        Ok(())
+    }
+
+    // Returns the input for the key event. Digits and ENTER can't be
+    // rebound, they are used to jump to a step in replays.
+    pub fn get(&self, event: &KeyEvent) -> Input {
+        let mut modifiers = event.modifiers;
+        // The shift key is already part of the character.
+        if let KeyCode::Char(_) = event.code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        if let Some((_, input)) = self
+            .bindings
+            .iter()
+            .find(|(key, _)| *key == (event.code, modifiers))
+        {
+            return *input;
+        }
+        match event.code {
+            KeyCode::Char(c @ '0'..='9') => Input::Digit(c as u8 - b'0'),
+            KeyCode::Enter => Input::Enter,
+            _ => Input::Unknown,
+        }
+    }
+
+    // Returns the first key bound to the input, for hints on the screen.
+    pub fn key(&self, input: Input) -> Option<String> {
+        self.bindings
+            .iter()
+            .find(|(_, i)| *i == input)
+            .map(|(key, _)| format_key(*key))
+    }
+
+    // Lists the keys of every action, one action per line.
+    pub fn describe(&self) -> String {
+        let mut keys: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for (key, input) in self.bindings.iter() {
+            if let Some(i) = ACTIONS.iter().position(|(_, a)| a == input) {
+                keys.entry(i).or_default().push(format_key(*key));
+            }
+        }
+        let mut s = String::new();
+        for (i, (name, _)) in ACTIONS.iter().enumerate() {
+            let keys = keys.get(&i).map_or(String::from("-"), |k| k.join(", "));
+            writeln!(s, "{:<10} {}", name, keys).unwrap();
+        }
+        s
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::preset(PRESETS[0]).unwrap()
+    }
+}
+
+// Names of keys other than characters.
+const KEY_NAMES: [(&str, KeyCode); 15] = [
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("space", KeyCode::Char(' ')),
+    ("esc", KeyCode::Esc),
+    ("enter", KeyCode::Enter),
+    ("tab", KeyCode::Tab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+];
+
+const MODIFIERS: [(&str, KeyModifiers); 3] = [
+    ("ctrl-", KeyModifiers::CONTROL),
+    ("alt-", KeyModifiers::ALT),
+    ("shift-", KeyModifiers::SHIFT),
+];
+
+// Parses a key like `q`, `space`, `f1` or `ctrl-c`.
+fn parse_key(s: &str) -> Result<Key, String> {
+    let mut rest = s;
+    let mut modifiers = KeyModifiers::NONE;
+    // A single `-` is the minus key, not the end of a modifier.
+    while let Some((prefix, modifier)) = MODIFIERS
+        .iter()
+        .find(|(prefix, _)| rest.len() > prefix.len() && rest.starts_with(prefix))
+    {
+        rest = &rest[prefix.len()..];
+        modifiers.insert(*modifier);
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match KEY_NAMES.iter().find(|(name, _)| *name == rest) {
+            Some((_, code)) => *code,
+            None => match rest.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                Some(n @ 1..=12) => KeyCode::F(n),
+                _ => return Err(format!("unknown key `{}`", s)),
+            },
+        },
+    };
+    // See Keymap::get().
+    if let KeyCode::Char(_) = code {
+        modifiers.remove(KeyModifiers::SHIFT);
+    }
+    Ok((code, modifiers))
+}
+
+// Formats the key the way parse_key() reads it.
+fn format_key(key: Key) -> String {
+    let (code, modifiers) = key;
+    let mut s = String::new();
+    for (prefix, modifier) in MODIFIERS.iter() {
+        if modifiers.contains(*modifier) {
+            s.push_str(prefix);
+        }
+    }
+    match KEY_NAMES.iter().find(|(_, c)| *c == code) {
+        Some((name, _)) => s.push_str(name),
+        None => match code {
+            KeyCode::Char(c) => s.push(c),
+            KeyCode::F(n) => write!(s, "f{}", n).unwrap(),
+            code => write!(s, "{:?}", code).unwrap(),
+        },
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn parse_and_format_keys() {
+        for key in [
+            "q",
+            "space",
+            "-",
+            "ctrl--",
+            "ctrl-c",
+            "alt-shift-up",
+            "f5",
+            "+",
+        ] {
+            assert_eq!(format_key(parse_key(key).unwrap()), key);
+        }
+        assert_eq!(
+            parse_key("shift-A").unwrap(),
+            (KeyCode::Char('A'), KeyModifiers::NONE)
+        );
+        assert_eq!(parse_key("ctrl-").unwrap_err(), "unknown key `ctrl-`");
+        assert_eq!(parse_key("f13").unwrap_err(), "unknown key `f13`");
+    }
+
+    #[test]
+    fn presets() {
+        let vim = Keymap::preset("vim").unwrap();
+        let none = KeyModifiers::NONE;
+        assert_eq!(
+            vim.get(&event(KeyCode::Char('h'), none)),
+            Input::ChangeDirection(0, Direction::West)
+        );
+        assert_eq!(
+            vim.get(&event(KeyCode::Up, none)),
+            Input::ChangeDirection(0, Direction::North)
+        );
+        assert_eq!(
+            vim.get(&event(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+            Input::Exit
+        );
+        assert_eq!(vim.get(&event(KeyCode::Char('c'), none)), Input::Unknown);
+        // The shift key is ignored for characters.
+        assert_eq!(
+            vim.get(&event(KeyCode::Char('+'), KeyModifiers::SHIFT)),
+            Input::IncreaseSpeed
+        );
+        assert_eq!(vim.get(&event(KeyCode::Char('7'), none)), Input::Digit(7));
+
+        let wasd = Keymap::preset("wasd").unwrap();
+        assert_eq!(
+            wasd.get(&event(KeyCode::Left, none)),
+            Input::ChangeDirection(1, Direction::West)
+        );
+        assert!(Keymap::preset("emacs").is_none());
+    }
+
+    #[test]
+    fn rebind_keys() {
+        let mut keymap = Keymap::default();
+        keymap
+            .rebind("pause", &[String::from("p"), String::from("q")])
+            .unwrap();
+        let none = KeyModifiers::NONE;
+        assert_eq!(keymap.get(&event(KeyCode::Char('p'), none)), Input::Pause);
+        assert_eq!(keymap.get(&event(KeyCode::Char('q'), none)), Input::Pause);
+        assert_eq!(keymap.get(&event(KeyCode::Char(' '), none)), Input::Unknown);
+        assert!(keymap.describe().contains("quit       esc, ctrl-c\n"));
+        assert_eq!(keymap.key(Input::Pause).as_deref(), Some("p"));
+        assert_eq!(keymap.key(Input::SaveReplay).as_deref(), Some("r"));
+        assert_eq!(
+            keymap.rebind("jump", &[]).unwrap_err(),
+            "unknown action `jump`"
+        );
+    }
+}
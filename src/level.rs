@@ -0,0 +1,298 @@
+//! Custom levels loaded from text files.
+//!
+//! A level is an ASCII map of the whole grid with one character per tile,
+//! each line of the file is a row of the grid:
+//!
+//! ```text
+//! ############
+//! #..........#
+//! #.sS.......#
+//! #....##....#
+//! #....##..F.#
+//! #..........#
+//! ############
+//! ```
+//!
+//! * `#` is an obstacle and `.` a free tile. The border isn't added
+//!   automatically, so a level without one wraps around the edges when it is
+//!   played with `--wrap`.
+//! * `S` is where the snake starts and `s` the tile behind it, so the snake
+//!   above starts heading east. In a level without a border `s` may be on
+//!   the opposite edge of `S`, the snake then starts crossing the edge.
+//! * `F` is where the first food is placed. It is optional, without it the
+//!   food is placed at random like in any other game.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::game::{create_grid, generate_successors};
+use crate::types::{Grid, Point, Snake, Tile};
+
+/// A grid layout with the start of the snake.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Level {
+    /// The obstacles and free tiles of the level, without the snake and
+    /// the food.
+    pub grid: Grid,
+    /// The snake at the start of the game, head first.
+    pub snake: Snake,
+    /// The point the first food is placed at.
+    pub food: Option<Point>,
+}
+
+impl Level {
+    /// Creates an empty level of the given size with a border of obstacles
+    /// and the snake in the middle, heading east.
+    pub fn new(width: usize, height: usize) -> Self {
+        let (x, y) = (width / 2, height / 2);
+        Level {
+            grid: create_grid(width, height),
+            snake: VecDeque::from([(x, y), (x - 1, y)]),
+            food: None,
+        }
+    }
+
+    /// Returns the width of the grid.
+    pub fn width(&self) -> usize {
+        self.grid.len()
+    }
+
+    /// Returns the height of the grid.
+    pub fn height(&self) -> usize {
+        self.grid[0].len()
+    }
+
+    /// Returns the free tiles the snake can't reach from its start.
+    pub fn unreachable(&self, wrap: bool) -> Vec<Point> {
+        let mut reached = vec![vec![false; self.height()]; self.width()];
+        let mut stack = vec![self.snake[0]];
+        while let Some((x, y)) = stack.pop() {
+            if reached[x][y] || self.grid[x][y] == Tile::Obstacle {
+                continue;
+            }
+            reached[x][y] = true;
+            stack.extend(generate_successors((x, y), &self.grid, wrap));
+        }
+
+        let mut points = Vec::new();
+        for (x, row) in self.grid.iter().enumerate() {
+            for (y, tile) in row.iter().enumerate() {
+                if *tile != Tile::Obstacle && !reached[x][y] {
+                    points.push((x, y));
+                }
+            }
+        }
+        points
+    }
+
+    /// Returns true if all tiles on the edge of the grid are obstacles. A
+    /// level without a border can only be played on a wrapping grid.
+    pub fn has_border(&self) -> bool {
+        let (width, height) = (self.width(), self.height());
+        self.grid.iter().enumerate().all(|(x, row)| {
+            row.iter().enumerate().all(|(y, tile)| {
+                let edge = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+                !edge || *tile == Tile::Obstacle
+            })
+        })
+    }
+}
+
+/// Formats the level as it is written to a level file.
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let c = if (x, y) == self.snake[0] {
+                    'S'
+                } else if (x, y) == self.snake[1] {
+                    's'
+                } else if Some((x, y)) == self.food {
+                    'F'
+                } else if self.grid[x][y] == Tile::Obstacle {
+                    '#'
+                } else {
+                    '.'
+                };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// An error in a level file.
+#[derive(Debug, Eq, PartialEq)]
+pub struct ParseError {
+    /// The line number, starting at 1.
+    pub line: usize,
+    /// The column, starting at 1. It is 0 if the whole line is wrong.
+    pub column: usize,
+    /// What is wrong at that point.
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.column {
+            0 => write!(f, "line {}: {}", self.line, self.message),
+            column => write!(f, "line {}, column {}: {}", self.line, column, self.message),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses the content of a level file.
+pub fn parse(s: &str) -> Result<Level, ParseError> {
+    let err = |line: usize, column: usize, message: String| ParseError {
+        line,
+        column,
+        message,
+    };
+
+    // Empty lines at the end of the file are ignored.
+    let lines: Vec<&str> = s
+        .trim_end()
+        .lines()
+        .map(|l| l.trim_end_matches('\r'))
+        .collect();
+    let width = lines.first().map_or(0, |l| l.chars().count());
+    if width == 0 {
+        return Err(err(1, 0, String::from("empty level")));
+    }
+
+    let mut grid = vec![vec![Tile::Free; lines.len()]; width];
+    let mut head: Option<Point> = None;
+    let mut tail: Option<Point> = None;
+    let mut food: Option<Point> = None;
+    for (y, line) in lines.iter().enumerate() {
+        let count = line.chars().count();
+        if count != width {
+            return Err(err(
+                y + 1,
+                0,
+                format!(
+                    "expected {} tiles like the first line, found {}",
+                    width, count
+                ),
+            ));
+        }
+        for (x, c) in line.chars().enumerate() {
+            // Each of these can only appear once.
+            let unique = match c {
+                '#' => {
+                    grid[x][y] = Tile::Obstacle;
+                    continue;
+                }
+                '.' => continue,
+                'S' => &mut head,
+                's' => &mut tail,
+                'F' => &mut food,
+                c => return Err(err(y + 1, x + 1, format!("unexpected character `{}`", c))),
+            };
+            if unique.is_some() {
+                return Err(err(y + 1, x + 1, format!("second `{}`", c)));
+            }
+            *unique = Some((x, y));
+        }
+    }
+
+    let Some(head) = head else {
+        return Err(err(lines.len(), 0, String::from("no snake start `S`")));
+    };
+    let (x, y) = head;
+    let tail = tail.ok_or_else(|| {
+        err(
+            y + 1,
+            x + 1,
+            String::from("no tail `s` behind the snake start"),
+        )
+    })?;
+    // Levels without a border are played with --wrap, there the tail may be
+    // across the edge.
+    if !generate_successors(head, &grid, true).contains(&tail) {
+        return Err(err(
+            tail.1 + 1,
+            tail.0 + 1,
+            String::from("the tail `s` must be next to the snake start `S`"),
+        ));
+    }
+
+    Ok(Level {
+        grid,
+        snake: VecDeque::from([head, tail]),
+        food,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_level_tail_across_edge() {
+        let level = parse("S...s\n.....\n....F").unwrap();
+        assert_eq!(level.snake, VecDeque::from([(0, 0), (4, 0)]));
+        assert!(!level.has_border());
+        let game = crate::game::Game::from_level(&level, true, 1, 1).unwrap();
+        assert_eq!(game.direction(), crate::types::Direction::East);
+    }
+
+    #[test]
+    fn parse_level() {
+        let level = parse("#####\n#Ss.#\n#..F#\n#####\n").unwrap();
+        assert_eq!((level.width(), level.height()), (5, 4));
+        assert_eq!(level.snake, VecDeque::from([(1, 1), (2, 1)]));
+        assert_eq!(level.food, Some((3, 2)));
+        assert_eq!(level.grid[0][0], Tile::Obstacle);
+        // The snake and the food aren't part of the grid.
+        assert_eq!(level.grid[1][1], Tile::Free);
+        assert_eq!(level.grid[3][2], Tile::Free);
+        assert!(level.has_border());
+        assert!(!parse("Ss...").unwrap().has_border());
+    }
+
+    #[test]
+    fn format_level() {
+        let map = "#####\n#Ss.#\n#..F#\n#####\n";
+        assert_eq!(parse(map).unwrap().to_string(), map);
+        let level = Level::new(12, 11);
+        assert_eq!(parse(&level.to_string()).unwrap(), level);
+    }
+
+    #[test]
+    fn find_unreachable_tiles() {
+        let level = parse("######\n#Ss#.#\n#..#.#\n######").unwrap();
+        assert_eq!(level.unreachable(false), [(4, 1), (4, 2)]);
+        // The right column can be reached over the edge.
+        let level = parse("...#..\n.Ss#..\n...#..").unwrap();
+        assert_eq!(level.unreachable(false).len(), 6);
+        assert!(level.unreachable(true).is_empty());
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |s: &str| parse(s).unwrap_err().to_string();
+        assert_eq!(error("\n\n"), "line 1: empty level");
+        assert_eq!(
+            error("#####\n#Ss#\n"),
+            "line 2: expected 5 tiles like the first line, found 4"
+        );
+        assert_eq!(
+            error("#####\n#Ssx#\n"),
+            "line 2, column 4: unexpected character `x`"
+        );
+        assert_eq!(error("#FsSF"), "line 1, column 5: second `F`");
+        assert_eq!(error("#...#\n#...#"), "line 2: no snake start `S`");
+        assert_eq!(
+            error("#...#\n#.S.#"),
+            "line 2, column 3: no tail `s` behind the snake start"
+        );
+        assert_eq!(
+            error("#s..#\n#..S#"),
+            "line 1, column 2: the tail `s` must be next to the snake start `S`"
+        );
+    }
+}
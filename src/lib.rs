@@ -0,0 +1,28 @@
+//! The engine behind the terminal snake game.
+//!
+//! Everything in here is independent of the terminal, so the game can be
+//! embedded in other tools, simulated in tests or driven by an autopilot:
+//!
+//! ```
+//! use snake::game::{Game, StepOutcome};
+//! use snake::path;
+//!
+//! let mut game = Game::new(20, 15, true, false, 42);
+//! while let Some(food) = game.food() {
+//!     let directions = path::find(game.grid(), *game.head(), food, game.wraps());
+//!     if let StepOutcome::Crashed(_) = game.step(directions.last().copied()) {
+//!         break;
+//!     }
+//! }
+//! ```
+#![warn(missing_docs)]
+
+pub mod config;
+pub mod cycle;
+pub mod game;
+pub mod level;
+pub mod path;
+pub mod replay;
+pub mod scores;
+pub mod strategy;
+pub mod types;
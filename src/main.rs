@@ -1,19 +1,136 @@
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
+use snake::config::Config;
+use snake::replay::Recorder;
+use snake::scores::Store;
+use std::env;
+use std::process;
 
+use crate::cli::Command;
+use crate::settings::Settings;
+
+mod bench;
 mod cli;
+mod edit;
 mod input;
+mod keymap;
 mod output;
-mod path;
-mod snake;
-mod types;
+mod play;
+mod playback;
+mod scoreboard;
+mod settings;
+mod theme;
 
 fn main() {
-    let opts = cli::Opts::parse();
+    let matches = cli::Opts::command().get_matches();
+    let mut opts = cli::Opts::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    match &opts.command {
+        Some(Command::Replay(replay_opts)) => {
+            let replay = playback::load(&replay_opts.file, replay_opts.game).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            });
+
+            let settings = load_settings(&opts);
+            let keymap = settings.keymap(None);
+            let theme = load_theme(&settings, None);
+
+            output::init();
+            playback::run(&replay, replay_opts.step, keymap, &theme);
+            output::reset();
+        }
+        Some(Command::Bench(bench_opts)) => bench::run(&bench_opts.into()),
+        Some(Command::Scores(scores_opts)) => {
+            let Some(store) = Store::default_path().map(Store::new) else {
+                eprintln!("Can't find the data directory for the high-score table");
+                process::exit(1);
+            };
+            if let Err(e) = scoreboard::print(&store, scores_opts.count) {
+                eprintln!("Can't read {}: {}", store.path().display(), e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Edit(edit_opts)) => {
+            let level = edit::load(&edit_opts.file, edit_opts.width, edit_opts.height)
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                });
+            let theme = load_theme(&load_settings(&opts), None);
+
+            output::init();
+            edit::run(&edit_opts.file, level, &theme);
+            output::reset();
+        }
+        None => {
+            let settings = load_settings(&opts);
+            settings.apply(&mut opts, &matches);
+            let keymap = settings.keymap(opts.keymap.as_deref());
+            if opts.print_keys {
+                print!("{}", keymap.describe());
+                return;
+            }
+            let theme = load_theme(&settings, opts.theme.as_deref());
+
+            let recorder = opts.record.as_ref().map(|path| {
+                Recorder::create(path).unwrap_or_else(|e| {
+                    eprintln!("Can't create replay file {}: {}", path.display(), e);
+                    process::exit(1);
+                })
+            });
 
-    output::init();
+            // Without a data directory the game is played without high scores.
+            let store = Store::default_path().map(Store::new);
+            let name = opts.name.clone().unwrap_or_else(|| {
+                env::var("USER")
+                    .or_else(|_| env::var("USERNAME"))
+                    .unwrap_or_else(|_| String::from("player"))
+            });
 
-    let config = snake::Config::from(opts);
-    snake::run(&config);
+            let mut config = Config::from(&opts);
+            if let Some(path) = &opts.level {
+                let level = play::load_level(path, opts.wrap).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                });
+                config.grid_width = level.width() as u16;
+                config.grid_height = level.height() as u16;
+                // The size of the level wins over fit-grid from the config file.
+                config.fit_grid = false;
+                config.level = Some(level);
+            }
+
+            let (grid_width, grid_height) = play::grid_size(&config);
+            let game = play::new_game(&config, grid_width, grid_height).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            });
+
+            output::init();
+            play::run(
+                &config,
+                game,
+                recorder,
+                store.as_ref(),
+                &name,
+                keymap,
+                &theme,
+            );
+            output::reset();
+        }
+    }
+}
+
+fn load_settings(opts: &cli::Opts) -> Settings {
+    settings::load(opts.config.as_deref()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    })
+}
 
-    output::reset();
+fn load_theme(settings: &Settings, name: Option<&str>) -> theme::Theme {
+    settings.theme(name).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    })
 }
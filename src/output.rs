@@ -1,87 +1,342 @@
-use crossterm::style::{Attribute, Print, StyledContent, Stylize};
+use crossterm::style::{ContentStyle, StyledContent, Stylize};
 use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
-use crossterm::{cursor, execute, style};
-use std::io::stdout;
+use crossterm::{cursor, execute, queue, style};
+use std::cell::RefCell;
+use std::io::{self, stdout, Write};
 
-use crate::types::{Point, Tile};
+use snake::types::{Point, Tile};
+
+use crate::theme::Theme;
 
 pub const MIN_GRID_WIDTH: u16 = 12;
 pub const MIN_GRID_HEIGHT: u16 = 11;
 
-pub struct Screen {
+// Draws the grid, the texts around it and the boxes on top of it. The
+// drawing goes into a canvas first, commit() shows what was drawn.
+pub trait Renderer {
+    fn canvas(&self) -> &Canvas;
+
+    // Shows everything drawn since the last commit.
+    fn commit(&self);
+
+    // Returns the status text drawn last.
+    fn status(&self) -> String {
+        self.canvas().status.borrow().clone()
+    }
+
+    fn draw_text_left(&self, str: String) {
+        let canvas = self.canvas();
+        canvas.print(
+            canvas.x_adjust,
+            canvas.y_adjust - 1,
+            ContentStyle::new(),
+            &str,
+        );
+    }
+
+    fn draw_text_right(&self, str: String) {
+        let canvas = self.canvas();
+        let x = (canvas.x_adjust + canvas.width * 2).saturating_sub(str.chars().count() as u16);
+        canvas.print(x, canvas.y_adjust - 1, ContentStyle::new(), &str);
+    }
+
+    // Draws the text on the line below the grid, replacing what was there before.
+    fn draw_status(&self, str: String) {
+        let canvas = self.canvas();
+        let y = canvas.y_adjust + canvas.height;
+        canvas.buffer.borrow_mut().clear_line(y);
+        canvas.print(canvas.x_adjust, y, ContentStyle::new(), &str);
+        canvas.status.replace(str);
+    }
+
+    // Clears the screen and shows the text in the top left corner, for when
+    // there is no room for the grid.
+    fn draw_message(&self, str: &str) {
+        let mut buffer = self.canvas().buffer.borrow_mut();
+        for y in 0..buffer.rows {
+            buffer.clear_line(y);
+        }
+        buffer.print(0, 0, ContentStyle::new(), str);
+    }
+
+    fn draw_tile(&self, p: Point, tile: Tile) {
+        let canvas = self.canvas();
+        canvas.draw_symbol(p, canvas.theme.tile(tile));
+    }
+
+    // Draws a part of the snake of the given player, every player has their own colour.
+    fn draw_snake(&self, p: Point, player: usize) {
+        let canvas = self.canvas();
+        canvas.draw_symbol(p, canvas.theme.snake(player));
+    }
+
+    // Draws the head of the snake of the given player.
+    fn draw_head(&self, p: Point, player: usize) {
+        let canvas = self.canvas();
+        canvas.draw_symbol(p, canvas.theme.head(player));
+    }
+
+    // Draws the lines in a box in the middle of the grid. Whatever doesn't fit
+    // into the grid is cut off.
+    fn draw_box(&self, lines: &[String]) {
+        let canvas = self.canvas();
+        let max_width = (canvas.width * 2 - 4) as usize;
+        let width = lines
+            .iter()
+            .map(|l| l.chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(max_width);
+        let height = lines.len().min(canvas.height as usize - 2);
+
+        let x = canvas.x_adjust + (canvas.width * 2 - width as u16 - 2) / 2;
+        let y = canvas.y_adjust + (canvas.height - height as u16) / 2;
+        let style = ContentStyle::new().reverse();
+        for (i, line) in lines.iter().take(height).enumerate() {
+            let line: String = line.chars().take(width).collect();
+            canvas.print(x, y + i as u16, style, &format!(" {:<width$} ", line));
+        }
+    }
+}
+
+// A grid centred in a screen of the given size.
+pub struct Canvas {
     width: u16,
+    height: u16,
     x_adjust: u16,
     y_adjust: u16,
+    // The last status text, so it can be drawn again after a resize.
+    status: RefCell<String>,
+    theme: Theme,
+    buffer: RefCell<Buffer>,
 }
 
-impl Screen {
-    pub fn new(width: u16, height: u16) -> Self {
+impl Canvas {
+    fn new(cols: u16, rows: u16, width: u16, height: u16, theme: &Theme) -> Self {
         // We use two characters to represent a tile. So we need to make sure to double
         // the x value when we actually draw the grid.
 
-        // Make sure we start with a blank screen.
-        execute!(stdout(), Clear(ClearType::All),).unwrap();
-
         // Calculate x and y adjustment needed to center the grid on screen.
-        let (cols, rows) = terminal::size().unwrap();
-        let x_adjust = (cols - width * 2) / 2;
-        let y_adjust = (rows - height + 1) / 2;
+        // A grid that doesn't fit starts in the top left corner.
+        let x_adjust = cols.saturating_sub(width * 2) / 2;
+        let y_adjust = rows.saturating_sub(height).div_ceil(2).max(1);
 
-        Screen {
+        Canvas {
             width,
+            height,
             x_adjust,
             y_adjust,
+            status: RefCell::new(String::new()),
+            theme: theme.clone(),
+            buffer: RefCell::new(Buffer::new(cols, rows)),
         }
     }
 
-    pub fn draw_text_left(&self, str: String) {
-        execute!(
-            stdout(),
-            cursor::MoveTo(self.x_adjust, self.y_adjust - 1),
-            Print(str),
-        )
-        .unwrap();
+    // Returns the column and row of the tile on the screen.
+    fn position(&self, p: Point) -> (u16, u16) {
+        let (x, y) = p;
+        (x as u16 * 2 + self.x_adjust, y as u16 + self.y_adjust)
     }
 
-    pub fn draw_text_right(&self, str: String) {
-        execute!(
-            stdout(),
-            cursor::MoveTo(
-                self.x_adjust + self.width * 2 - str.chars().count() as u16,
-                self.y_adjust - 1
-            ),
-            Print(str),
-        )
-        .unwrap()
+    fn draw_symbol(&self, p: Point, symbol: StyledContent<&str>) {
+        let (x, y) = self.position(p);
+        self.print(x, y, *symbol.style(), symbol.content());
     }
 
-    pub fn draw_tile(&self, p: Point, tile: Tile) {
-        // We use two characters to represent a tile. So we need to make sure to double
-        // the x value when we actually draw the grid.
-        let (x, y) = p;
-        execute!(
-            stdout(),
-            cursor::MoveTo(x as u16 * 2 + self.x_adjust, y as u16 + self.y_adjust),
-            style::PrintStyledContent(tile_to_symbol(tile)),
-        )
-        .unwrap()
+    fn print(&self, x: u16, y: u16, style: ContentStyle, str: &str) {
+        self.buffer.borrow_mut().print(x, y, style, str);
     }
 }
 
-// Returns the actual characters to be drawn for the given tile.
-fn tile_to_symbol(tile: Tile) -> StyledContent<&'static str> {
-    match tile {
-        Tile::Free => "  ".attribute(Attribute::Reset),
-        Tile::Snake => "██".green(),
-        Tile::Food => "██".yellow(),
-        Tile::Obstacle => "▓▓".white(),
-        Tile::Crash => "XX".red().on_white(),
+// Draws on the terminal.
+pub struct Screen {
+    canvas: Canvas,
+}
+
+impl Screen {
+    pub fn new(width: u16, height: u16, theme: &Theme) -> Self {
+        let (cols, rows) = terminal::size().unwrap();
+        // The new buffer clears the screen with the first commit.
+        Screen {
+            canvas: Canvas::new(cols, rows, width, height, theme),
+        }
+    }
+
+    // Shows the terminal cursor on the given tile. It is moved right away,
+    // so this has to come after commit().
+    pub fn show_cursor(&self, p: Point) {
+        let (x, y) = self.canvas.position(p);
+        execute!(stdout(), cursor::MoveTo(x, y), cursor::Show).unwrap()
+    }
+}
+
+impl Renderer for Screen {
+    fn canvas(&self) -> &Canvas {
+        &self.canvas
+    }
+
+    // Writes the changed cells to the terminal, with a single flush.
+    fn commit(&self) {
+        let mut frame = Vec::new();
+        self.canvas.buffer.borrow_mut().commit(&mut frame).unwrap();
+        if !frame.is_empty() {
+            let mut stdout = stdout().lock();
+            stdout.write_all(&frame).unwrap();
+            stdout.flush().unwrap();
+        }
     }
 }
 
+// Draws into memory, so tests can check what would be on the screen.
+#[cfg(test)]
+pub struct MemoryScreen {
+    canvas: Canvas,
+}
+
+#[cfg(test)]
+impl MemoryScreen {
+    pub fn new(cols: u16, rows: u16, width: u16, height: u16, theme: &Theme) -> Self {
+        MemoryScreen {
+            canvas: Canvas::new(cols, rows, width, height, theme),
+        }
+    }
+
+    // Returns the characters on the screen after the last commit, without
+    // the spaces at the end of the lines.
+    pub fn frame(&self) -> String {
+        let buffer = self.canvas.buffer.borrow();
+        let mut frame = String::new();
+        for row in buffer.front.chunks(buffer.cols as usize) {
+            let line: String = row.iter().map(|cell| cell.c).collect();
+            frame.push_str(line.trim_end());
+            frame.push('\n');
+        }
+        frame
+    }
+}
+
+#[cfg(test)]
+impl Renderer for MemoryScreen {
+    fn canvas(&self) -> &Canvas {
+        &self.canvas
+    }
+
+    fn commit(&self) {
+        let mut buffer = self.canvas.buffer.borrow_mut();
+        buffer.commit(&mut io::sink()).unwrap();
+    }
+}
+
+// A character on the screen and how it is styled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cell {
+    c: char,
+    style: ContentStyle,
+}
+
+// An empty cell, as on a cleared screen.
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            c: ' ',
+            style: ContentStyle::new(),
+        }
+    }
+}
+
+// The cells of the whole terminal, as they are shown (front) and as they
+// have been drawn since (back).
+struct Buffer {
+    cols: u16,
+    rows: u16,
+    front: Vec<Cell>,
+    back: Vec<Cell>,
+    // Set until the terminal has been cleared for the first time.
+    clear: bool,
+}
+
+impl Buffer {
+    fn new(cols: u16, rows: u16) -> Self {
+        let cells = vec![Cell::default(); cols as usize * rows as usize];
+        Buffer {
+            cols,
+            rows,
+            front: cells.clone(),
+            back: cells,
+            clear: true,
+        }
+    }
+
+    // Draws the text starting at the given position. Whatever doesn't fit
+    // on the screen is cut off.
+    fn print(&mut self, x: u16, y: u16, style: ContentStyle, str: &str) {
+        if y >= self.rows {
+            return;
+        }
+        let start = y as usize * self.cols as usize;
+        for (x, c) in (x..self.cols).zip(str.chars()) {
+            self.back[start + x as usize] = Cell { c, style };
+        }
+    }
+
+    fn clear_line(&mut self, y: u16) {
+        if y < self.rows {
+            let start = y as usize * self.cols as usize;
+            self.back[start..start + self.cols as usize].fill(Cell::default());
+        }
+    }
+
+    // Writes the commands that change the cells that differ from what is on
+    // the screen. Runs of changed cells with the same style are written
+    // together.
+    fn commit(&mut self, out: &mut impl Write) -> io::Result<()> {
+        if self.clear {
+            queue!(out, Clear(ClearType::All))?;
+            self.clear = false;
+        }
+        let cols = self.cols as usize;
+        for (y, (front, back)) in self
+            .front
+            .chunks_mut(cols)
+            .zip(self.back.chunks(cols))
+            .enumerate()
+        {
+            let mut x = 0;
+            while x < cols {
+                if front[x] == back[x] {
+                    x += 1;
+                    continue;
+                }
+                let start = x;
+                let style = back[x].style;
+                let mut run = String::new();
+                while x < cols && front[x] != back[x] && back[x].style == style {
+                    run.push(back[x].c);
+                    front[x] = back[x];
+                    x += 1;
+                }
+                queue!(
+                    out,
+                    cursor::MoveTo(start as u16, y as u16),
+                    style::PrintStyledContent(StyledContent::new(style, run)),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Returns the largest grid that fits on the screen. We need one line above
+// the grid and one below it for the status texts.
 pub fn max_grid_size() -> (u16, u16) {
     let (cols, rows) = terminal::size().unwrap();
-    (cols / 2, rows - 1)
+    (cols / 2, rows.saturating_sub(2))
+}
+
+// Returns true if a grid of the given size fits on the screen.
+pub fn fits(width: u16, height: u16) -> bool {
+    let (max_width, max_height) = max_grid_size();
+    width <= max_width && height <= max_height
 }
 
 pub fn init() {
@@ -93,3 +348,86 @@ pub fn reset() {
     execute!(stdout(), cursor::Show, LeaveAlternateScreen).unwrap();
     terminal::disable_raw_mode().unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit_writes_only_changed_cells() {
+        let theme = Theme::default();
+        let mut buffer = Buffer::new(80, 24);
+        let draw = |buffer: &mut Buffer, p: (u16, u16), tile: Tile| {
+            let symbol = theme.tile(tile);
+            buffer.print(p.0 * 2, p.1, *symbol.style(), symbol.content());
+        };
+        // A 20x15 grid with a border.
+        for x in 0..20 {
+            for y in 0..15 {
+                let border = x == 0 || y == 0 || x == 19 || y == 14;
+                draw(
+                    &mut buffer,
+                    (x, y),
+                    if border { Tile::Obstacle } else { Tile::Free },
+                );
+            }
+        }
+        let mut full = Vec::new();
+        buffer.commit(&mut full).unwrap();
+
+        // Drawing the whole grid again changes nothing.
+        for x in 1..19 {
+            for y in 1..14 {
+                draw(&mut buffer, (x, y), Tile::Free);
+            }
+        }
+        let mut unchanged = Vec::new();
+        buffer.commit(&mut unchanged).unwrap();
+        assert!(unchanged.is_empty());
+
+        // A step moves the head and the tail of the snake.
+        draw(&mut buffer, (5, 5), Tile::Snake);
+        draw(&mut buffer, (3, 5), Tile::Free);
+        let mut step = Vec::new();
+        buffer.commit(&mut step).unwrap();
+        assert!(
+            step.len() * 20 < full.len(),
+            "{} {}",
+            step.len(),
+            full.len()
+        );
+        assert!(String::from_utf8(step).unwrap().contains("██"));
+    }
+
+    #[test]
+    fn draw_texts_and_box() {
+        let screen = MemoryScreen::new(16, 8, 6, 5, &Theme::builtin("ascii").unwrap());
+        for x in 0..6 {
+            for y in 0..5 {
+                screen.draw_tile((x, y), Tile::Obstacle);
+            }
+        }
+        screen.draw_text_left(String::from("L"));
+        screen.draw_text_right(String::from("R"));
+        screen.draw_status(String::from("A long status"));
+        // The new status replaces the whole line.
+        screen.draw_status(String::from("Short"));
+        // Lines are cut off at the edge of the grid.
+        screen.draw_box(&[String::from("Game over"), String::from("!")]);
+        screen.commit();
+        assert_eq!(
+            screen.frame().lines().collect::<Vec<_>>(),
+            [
+                "",
+                "  L          R",
+                "  ############",
+                "  # Game ove #",
+                "  # !        #",
+                "  ############",
+                "  ############",
+                "  Short",
+            ]
+        );
+        assert_eq!(screen.status(), "Short");
+    }
+}
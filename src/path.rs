@@ -1,46 +1,96 @@
-use crate::snake::{generate_successors, next_point};
-use crate::types::{Direction, Grid, Point, Tile};
-
-// Calculates a path from the start position to the target on the grid using the A* Search Algorithm.
-// The result is a vector of directions. If no path can be found an empty vector is returned.
-//
-// --> https://www.geeksforgeeks.org/a-search-algorithm/
-// g: The movement cost to move from the starting point to this point on the grid,
-//    following the path generated to get there.
-// h: The estimated movement cost to move from this point on the grid to the final destination.
-//    We currently use manhatten distance as an approximation heuristic.
-// f: The search algorith picks the next point having the lowest 'f' and proceeds with that.
-pub fn find(grid: &Grid, start: Point, target: Point) -> Vec<Direction> {
-    let (start_x, start_y) = start;
-    let grid_width = grid.len();
+//! Pathfinding for the autopilot.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+use crate::game::{generate_successors, next_point};
+use crate::types::{Direction, Grid, Point, Snake, Tile};
+
+/// Calculates a path from the start position to the target on the grid using the A* Search Algorithm.
+/// The result is a vector of directions in reverse order, so that a `pop()` returns the next one.
+/// If no path can be found the longest free straight path is returned, which may be empty.
+/// With `wrap` the path may leave the grid on one edge and re-enter it on the opposite side.
+///
+/// --> <https://www.geeksforgeeks.org/a-search-algorithm/>
+/// - g: The movement cost to move from the starting point to this point on the grid,
+///   following the path generated to get there.
+/// - h: The estimated movement cost to move from this point on the grid to the final destination.
+///   We currently use manhatten distance as an approximation heuristic, which
+///   on a wrapping grid takes the shorter way around in each dimension.
+/// - f: The search algorith picks the next point having the lowest 'f' and proceeds with that.
+pub fn find(grid: &Grid, start: Point, target: Point, wrap: bool) -> Vec<Direction> {
+    // If we can't find a clear path we fallback to to longest free straight path.
+    search(grid, start, target, wrap).unwrap_or_else(|| best_straight_path(grid, start, wrap))
+}
+
+/// Like [`find`], but only returns the path to the food if the snake can
+/// still escape after eating it.
+///
+/// To check this a copy of the snake follows the path. Afterwards there
+/// must be a path from its head to its tail, or at least as many tiles
+/// reachable as the snake is long. Otherwise the snake chases its own tail
+/// to buy time until the food is safe to get. In that case only the next
+/// direction is returned, so the path is recalculated after every step.
+pub fn find_safe(grid: &Grid, snake: &Snake, food: Point, wrap: bool) -> Vec<Direction> {
+    let head = *snake.front().unwrap();
+
+    if let Some(path) = search(grid, head, food, wrap) {
+        let (grid, snake) = simulate(grid, snake, &path, wrap);
+        if can_escape(&grid, &snake, wrap) {
+            return path;
+        }
+    }
+
+    chase_tail(grid, snake, wrap).unwrap_or_else(|| best_straight_path(grid, head, wrap))
+}
+
+// The A* search behind find(). Returns None if there is no path to the target.
+// The target itself may be blocked, which allows us to search for the tail of the snake.
+fn search(grid: &Grid, start: Point, target: Point, wrap: bool) -> Option<Vec<Direction>> {
     let grid_height = grid[0].len();
+    let cells = grid.len() * grid_height;
+    // The details of the points are kept in flat arrays, indexed by x * height + y.
+    let index = |(x, y): Point| x * grid_height + y;
 
-    // Create a bunch of 2D arrays to hold the details of a point.
-    let mut parents = vec![vec![None; grid_height]; grid_width];
-    let mut g_list = vec![vec![0; grid_height]; grid_width];
-    let mut f_list = vec![vec![i32::MAX; grid_height]; grid_width];
+    // Create a bunch of arrays to hold the details of a point.
+    let mut parents: Vec<Option<Point>> = vec![None; cells];
+    let mut g_list = vec![0; cells];
+    let mut f_list = vec![i32::MAX; cells];
 
     // Create a closed list to hold already checked points.
-    let mut closed = vec![vec![false; grid_height]; grid_width];
+    let mut closed = vec![false; cells];
 
-    // Create a open list to hold potential points of the path.
-    // A value of true mean that the point is in the open list.
-    let mut open = vec![vec![false; grid_height]; grid_width];
+    // Create a open list to hold potential points of the path, ordered by their
+    // f value. Ties are broken by the position of the point, so the search
+    // picks the same path on every run.
+    let mut open = BinaryHeap::new();
 
     // Put the starting point on the open list with a f value of 0.
-    open[start_x][start_y] = true;
-    f_list[start_x][start_y] = 0;
+    f_list[index(start)] = 0;
+    open.push(Reverse((0, start)));
 
     // Pop the point with the lowest f value off the open list.
-    while let Some(p) = get_lowest_f(&mut open, &f_list) {
-        let (x, y) = p;
+    while let Some(Reverse((f, p))) = open.pop() {
+        let i = index(p);
+
+        // A point is pushed again whenever we find a lower f value for it.
+        // Skip the outdated entries.
+        if closed[i] || f > f_list[i] {
+            continue;
+        }
 
         // Push it on the closed list.
-        closed[x][y] = true;
+        closed[i] = true;
 
         // Go through all successors for that point.
-        for s in generate_successors(p, grid).iter() {
-            let (s_x, s_y) = *s;
+        for s in generate_successors(p, grid, wrap).iter() {
+            let s_i = index(*s);
+
+            // If successor is the target, stop and generate the path.
+            if *s == target {
+                parents[s_i] = Some(p);
+                return Some(generate_path(grid, *s, &parents, wrap));
+            }
 
             // Skip blocked tiles.
             if blocked_tile(grid, *s) {
@@ -48,71 +98,114 @@ pub fn find(grid: &Grid, start: Point, target: Point) -> Vec<Direction> {
             }
 
             // If the successor is already on the closed list, ignore it.
-            if closed[s_x][s_y] {
+            if closed[s_i] {
                 continue;
             }
 
-            // If successor is the target, stop and generate the path.
-            if *s == target {
-                parents[s_x][s_y] = Some(p);
-                return generate_path(*s, &parents);
-            }
-
             // Compute g,h and f for the successor.
-            let g = g_list[x][y] + 1;
-            let h = manhatten_distance(*s, target);
+            let g = g_list[i] + 1;
+            let h = manhatten_distance(grid, *s, target, wrap);
             let f = g + h;
 
             // If the known f value is lower than what we currently have for the position.
-            if f < f_list[s_x][s_y] {
+            if f < f_list[s_i] {
                 // Update the details of this position with the values of the successor.
-                g_list[s_x][s_y] = g;
-                f_list[s_x][s_y] = f;
-                parents[s_x][s_y] = Some(p);
+                g_list[s_i] = g;
+                f_list[s_i] = f;
+                parents[s_i] = Some(p);
 
                 // And push it on the open list.
-                open[s_x][s_y] = true;
+                open.push(Reverse((f, *s)));
             }
         }
     }
 
     // If we reach this point we couldn't find a clear path.
-    // We fallback to to longest free straight path.
-    best_straight_path(grid, start)
+    None
 }
 
-// Finds the point with the lowest f value in the list and returns it.
-fn get_lowest_f(list: &mut [Vec<bool>], f_list: &[Vec<i32>]) -> Option<Point> {
-    let mut lowest_f = i32::MAX;
-    let mut res: Option<Point> = None;
-    for (x, y_list) in list.iter().enumerate() {
-        for (y, value) in y_list.iter().enumerate() {
-            if !(*value) {
-                continue;
-            }
-            let f = f_list[x][y];
-            if f < lowest_f {
-                lowest_f = f;
-                res = Some((x, y));
-            }
+// Moves a copy of the snake along the path and returns the resulting grid and snake.
+// The snake grows when it passes food, but no new food is placed.
+fn simulate(grid: &Grid, snake: &Snake, path: &[Direction], wrap: bool) -> (Grid, Snake) {
+    let mut grid = grid.clone();
+    let mut snake = snake.clone();
+    // The path is in reverse order.
+    for d in path.iter().rev() {
+        let p = next_point(*snake.front().unwrap(), *d, &grid, wrap);
+        let (x, y) = p;
+        if grid[x][y] != Tile::Food {
+            let (tail_x, tail_y) = snake.pop_back().unwrap();
+            grid[tail_x][tail_y] = Tile::Free;
         }
+        grid[x][y] = Tile::Snake;
+        snake.push_front(p);
     }
-    if let Some((x, y)) = res {
-        list[x][y] = false
+    (grid, snake)
+}
+
+// Checks if the snake can follow its tail or has enough room to move around.
+fn can_escape(grid: &Grid, snake: &Snake, wrap: bool) -> bool {
+    let head = *snake.front().unwrap();
+    let tail = *snake.back().unwrap();
+    search(grid, head, tail, wrap).is_some() || reachable_area(grid, head, wrap) >= snake.len()
+}
+
+// Picks the next step that keeps the snake away from its tail for as long
+// as possible, while still being able to reach it. If the tail can't be
+// reached from anywhere the step with the most room is taken.
+fn chase_tail(grid: &Grid, snake: &Snake, wrap: bool) -> Option<Vec<Direction>> {
+    let head = *snake.front().unwrap();
+    let mut best: Option<((bool, usize), Direction)> = None;
+    for p in generate_successors(head, grid, wrap) {
+        if blocked_tile(grid, p) {
+            continue;
+        }
+        let d = get_direction(head, p, wrap);
+        let (grid, snake) = simulate(grid, snake, &[d], wrap);
+        let score = match search(&grid, p, *snake.back().unwrap(), wrap) {
+            Some(path) => (true, path.len()),
+            None => (false, reachable_area(&grid, p, wrap)),
+        };
+        if best.is_none_or(|(s, _)| score > s) {
+            best = Some((score, d));
+        }
     }
-    res
+    best.map(|(_, d)| vec![d])
+}
+
+// Counts the tiles reachable from the start point.
+fn reachable_area(grid: &Grid, start: Point, wrap: bool) -> usize {
+    let mut seen = vec![vec![false; grid[0].len()]; grid.len()];
+    let mut queue = VecDeque::from([start]);
+    let mut count = 0;
+    while let Some(p) = queue.pop_front() {
+        for (x, y) in generate_successors(p, grid, wrap) {
+            if !seen[x][y] && !blocked_tile(grid, (x, y)) {
+                seen[x][y] = true;
+                count += 1;
+                queue.push_back((x, y));
+            }
+        }
+    }
+    count
 }
 
 // Generates the path from the starting point to the target as a vector of directions.
 // The entries are in reverse order so that a pop() on the vector returns the next direction.
-fn generate_path(target: Point, parents: &[Vec<Option<Point>>]) -> Vec<Direction> {
+fn generate_path(
+    grid: &Grid,
+    target: Point,
+    parents: &[Option<Point>],
+    wrap: bool,
+) -> Vec<Direction> {
+    let grid_height = grid[0].len();
     let mut directions: Vec<Direction> = Vec::new();
     let mut p = target;
     loop {
         let (x, y) = p;
-        match parents[x][y] {
+        match parents[x * grid_height + y] {
             Some(parent) => {
-                let direction = get_direction(parent, p);
+                let direction = get_direction(parent, p, wrap);
                 directions.push(direction);
                 p = parent;
             }
@@ -122,16 +215,19 @@ fn generate_path(target: Point, parents: &[Vec<Option<Point>>]) -> Vec<Direction
     directions
 }
 
-fn best_straight_path(grid: &Grid, start: Point) -> Vec<Direction> {
+fn best_straight_path(grid: &Grid, start: Point, wrap: bool) -> Vec<Direction> {
+    // On a wrapping grid a free row or column is endless. Once we are back
+    // at the start there is no point in looking further.
+    let limit = grid.len().max(grid[0].len());
     let mut direction = None;
     let mut count = 0;
-    for p in generate_successors(start, grid) {
-        let d = get_direction(start, p);
+    for p in generate_successors(start, grid, wrap) {
+        let d = get_direction(start, p, wrap);
         let mut n = p;
         let mut c = 0;
-        while !blocked_tile(grid, n) {
+        while !blocked_tile(grid, n) && c < limit {
             c += 1;
-            n = next_point(n, d);
+            n = next_point(n, d, grid, wrap);
         }
         if c > count {
             count = c;
@@ -144,10 +240,13 @@ fn best_straight_path(grid: &Grid, start: Point) -> Vec<Direction> {
     }
 }
 
-fn get_direction(from: Point, to: Point) -> Direction {
+// Returns the direction from a point to one of its successors.
+pub(crate) fn get_direction(from: Point, to: Point, wrap: bool) -> Direction {
     let (from_x, from_y) = from;
     let (to_x, to_y) = to;
-    if to_x > from_x {
+    // On a wrapping grid the successor may be on the opposite edge.
+    let wrapped = wrap && (from_x.abs_diff(to_x) > 1 || from_y.abs_diff(to_y) > 1);
+    let direction = if to_x > from_x {
         Direction::East
     } else if to_x < from_x {
         Direction::West
@@ -155,18 +254,27 @@ fn get_direction(from: Point, to: Point) -> Direction {
         Direction::South
     } else {
         Direction::North
+    };
+    if wrapped {
+        direction.opposite()
+    } else {
+        direction
     }
 }
 
-fn manhatten_distance(from: Point, to: Point) -> i32 {
+fn manhatten_distance(grid: &Grid, from: Point, to: Point, wrap: bool) -> i32 {
     let (from_x, from_y) = from;
     let (to_x, to_y) = to;
-    let dx = (from_x as i32 - to_x as i32).abs();
-    let dy = (from_y as i32 - to_y as i32).abs();
-    dx + dy
+    let mut dx = from_x.abs_diff(to_x);
+    let mut dy = from_y.abs_diff(to_y);
+    if wrap {
+        dx = dx.min(grid.len() - dx);
+        dy = dy.min(grid[0].len() - dy);
+    }
+    (dx + dy) as i32
 }
 
-fn blocked_tile(grid: &Grid, p: Point) -> bool {
+pub(crate) fn blocked_tile(grid: &Grid, p: Point) -> bool {
     let (x, y) = p;
     grid[x][y] != Tile::Free && grid[x][y] != Tile::Food
 }
@@ -180,7 +288,7 @@ mod tests {
         let mut grid = vec![vec![Tile::Free; 3]; 3];
         grid[2][0] = Tile::Food;
         assert_eq!(
-            find(&grid, (0, 0), (2, 0)),
+            find(&grid, (0, 0), (2, 0), false),
             vec![Direction::East, Direction::East]
         )
     }
@@ -192,7 +300,7 @@ mod tests {
         grid[1][1] = Tile::Obstacle;
         grid[2][0] = Tile::Food;
         assert_eq!(
-            find(&grid, (0, 0), (2, 0)),
+            find(&grid, (0, 0), (2, 0), false),
             vec![
                 Direction::North,
                 Direction::North,
@@ -211,7 +319,7 @@ mod tests {
         grid[1][2] = Tile::Obstacle;
         grid[0][2] = Tile::Food;
         assert_eq!(
-            find(&grid, (2, 2), (0, 2)),
+            find(&grid, (2, 2), (0, 2), false),
             vec![
                 Direction::South,
                 Direction::South,
@@ -223,12 +331,78 @@ mod tests {
         )
     }
 
+    #[test]
+    fn solve_path_wrapping() {
+        let mut grid = vec![vec![Tile::Free; 5]; 5];
+        grid[4][0] = Tile::Food;
+        assert_eq!(find(&grid, (0, 0), (4, 0), true), vec![Direction::West]);
+        assert_eq!(
+            find(&grid, (1, 1), (4, 0), true),
+            vec![Direction::West, Direction::North, Direction::West]
+        );
+    }
+
+    #[test]
+    fn find_safe_takes_safe_path() {
+        let mut grid = vec![vec![Tile::Free; 5]; 5];
+        grid[0][0] = Tile::Snake;
+        grid[1][0] = Tile::Snake;
+        grid[3][0] = Tile::Food;
+        let snake = VecDeque::from([(1, 0), (0, 0)]);
+        assert_eq!(
+            find_safe(&grid, &snake, (3, 0), false),
+            vec![Direction::East, Direction::East]
+        )
+    }
+
+    #[test]
+    fn find_safe_avoids_dead_end() {
+        // The food is at the end of a dead end. After eating it the snake
+        // would block the way out with its own body.
+        let mut grid = vec![vec![Tile::Free; 3]; 5];
+        grid[4][0] = Tile::Obstacle;
+        grid[4][2] = Tile::Obstacle;
+        grid[0][1] = Tile::Snake;
+        grid[1][1] = Tile::Snake;
+        grid[2][1] = Tile::Snake;
+        grid[4][1] = Tile::Food;
+        let snake = VecDeque::from([(2, 1), (1, 1), (0, 1)]);
+        assert_eq!(
+            find(&grid, (2, 1), (4, 1), false),
+            vec![Direction::East, Direction::East]
+        );
+        // Chase the tail the long way round instead.
+        assert_eq!(
+            find_safe(&grid, &snake, (4, 1), false),
+            vec![Direction::East]
+        );
+    }
+
+    #[test]
+    fn reachable_area_counts_free_tiles() {
+        let mut grid = vec![vec![Tile::Free; 3]; 3];
+        grid[1][0] = Tile::Obstacle;
+        grid[1][1] = Tile::Obstacle;
+        grid[1][2] = Tile::Obstacle;
+        assert_eq!(reachable_area(&grid, (0, 0), false), 3);
+    }
+
     #[test]
     fn best_straight_path_none() {
         let mut grid = vec![vec![Tile::Free; 3]; 3];
         grid[0][1] = Tile::Obstacle;
         grid[1][0] = Tile::Obstacle;
-        assert_eq!(best_straight_path(&grid, (0, 0)), vec![])
+        assert_eq!(best_straight_path(&grid, (0, 0), false), vec![])
+    }
+
+    #[test]
+    fn best_straight_path_wrapping() {
+        let mut grid = vec![vec![Tile::Free; 3]; 3];
+        grid[0][1] = Tile::Obstacle;
+        assert_eq!(
+            best_straight_path(&grid, (0, 0), true),
+            vec![Direction::West]
+        )
     }
 
     #[test]
@@ -238,6 +412,9 @@ mod tests {
         grid[6][4] = Tile::Obstacle;
         grid[4][7] = Tile::Obstacle;
         grid[0][4] = Tile::Obstacle;
-        assert_eq!(best_straight_path(&grid, (4, 4)), vec![Direction::West])
+        assert_eq!(
+            best_straight_path(&grid, (4, 4), false),
+            vec![Direction::West]
+        )
     }
 }
@@ -0,0 +1,927 @@
+use rand::random;
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicU16};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use snake::config::{Autopilot, Config, MIN_INTERVAL};
+use snake::game::{Game, StepOutcome};
+use snake::level::{self, Level};
+use snake::replay::{Event, Recorder};
+use snake::scores::{self, Key, Score, Store};
+use snake::strategy::{self, Strategy};
+use snake::types::{Direction, Point, Tile};
+
+use crate::cli::MAX_INTERVAL;
+use crate::input::{self, Input};
+use crate::keymap::Keymap;
+use crate::output::{self, Renderer, Screen};
+use crate::theme::Theme;
+
+// Number of high scores shown when the game is over.
+const HIGH_SCORES: usize = 5;
+
+pub fn run(
+    config: &Config,
+    mut game: Game,
+    recorder: Option<Recorder<File>>,
+    store: Option<&Store>,
+    name: &str,
+    keymap: Keymap,
+    theme: &Theme,
+) {
+    let mut grid_width = game.grid().len() as u16;
+    let mut grid_height = game.grid()[0].len() as u16;
+    // Levels aren't part of the high-score table, their grids differ from
+    // the random ones of the same size.
+    let store = if config.level.is_some() { None } else { store };
+
+    let interval = Arc::new(AtomicU16::new(config.interval));
+
+    let mut paused = false;
+
+    // The config as it is written to the replay file, with the actual grid size.
+    let mut replay_config = Config {
+        grid_width,
+        grid_height,
+        fit_grid: false,
+        ..config.clone()
+    };
+
+    // Only the games --record accepts can be saved as replays.
+    let can_save = humans(config) == 1 && config.opponents == 0 && config.level.is_none();
+    let mut recording = Recording::new(recorder);
+
+    recording.start(&replay_config, &game);
+    let mut screen = Screen::new(grid_width, grid_height, theme);
+    let humans = humans(config);
+    draw_game(&screen, &game, humans);
+
+    let (tx, rx) = mpsc::channel();
+
+    let hints = key_hints(&keymap, can_save);
+    // Spawn thread to handle ui input.
+    input::handle(tx.clone(), keymap);
+
+    // Spawn thread to send ticks.
+    input::send_ticks(tx, Arc::clone(&interval));
+
+    // The turns requested by every player. Each step takes one of them.
+    let mut turns = new_turns(config, &game);
+    let mut autopilot = create_strategy(config, &game, &screen);
+    let mut opponents = create_opponents(config, &game);
+    // While counting down ticks are ignored, but directions are accepted.
+    let mut countdown = start_countdown(config, &screen, &game);
+    // Set while the grid doesn't fit on the screen, the game stays paused.
+    let mut too_small = false;
+    // With --fit-grid the size of the resized screen, for the next game.
+    let mut new_size: Option<(u16, u16)> = None;
+    // The status to show again when the game is resumed after a resize.
+    let mut paused_status: Option<String> = None;
+    let mut stats = Stats::default();
+    // The summary shown when the game is over, drawn again after a resize.
+    let mut summary: Option<Vec<String>> = None;
+
+    loop {
+        if let Some(e) = recording.take_error() {
+            screen.draw_status(e);
+        }
+        // Everything drawn for the last input is shown at once.
+        screen.commit();
+        let input = rx.recv().unwrap();
+        match input {
+            Input::Unknown | Input::Digit(_) => {}
+            Input::Exit => {
+                recording.record(&game, Event::Quit);
+                recording.flush();
+                break;
+            }
+            // With a single player both sets of keys steer the snake.
+            Input::ChangeDirection(player, d) => {
+                let player = if humans == 1 { 0 } else { player };
+                if let Some(queue) = turns.get_mut(player) {
+                    queue.push(d, game.directions()[player]);
+                }
+            }
+            Input::Pause | Input::Enter => {
+                if too_small {
+                    continue;
+                }
+                // ENTER only starts a game of the new size.
+                let resized = input == Input::Enter && new_size.is_some();
+                if resized {
+                    (grid_width, grid_height) = new_size.take().unwrap();
+                    replay_config.grid_width = grid_width;
+                    replay_config.grid_height = grid_height;
+                }
+                if game.is_over() || resized {
+                    // restart game
+                    game = match new_game(config, grid_width, grid_height) {
+                        Ok(game) => game,
+                        Err(e) => {
+                            screen.draw_status(e);
+                            continue;
+                        }
+                    };
+                    interval.store(config.interval, atomic::Ordering::Relaxed);
+                    paused = false;
+                    recording.start(&replay_config, &game);
+                    stats = Stats::default();
+                    summary = None;
+                    screen = Screen::new(grid_width, grid_height, theme);
+                    draw_game(&screen, &game, humans);
+                    turns = new_turns(config, &game);
+                    autopilot = create_strategy(config, &game, &screen);
+                    opponents = create_opponents(config, &game);
+                    countdown = start_countdown(config, &screen, &game);
+                    continue;
+                }
+                if input == Input::Enter {
+                    continue;
+                }
+                // pause / resume
+                paused ^= true;
+                if !paused {
+                    if let Some(status) = paused_status.take() {
+                        screen.draw_status(status);
+                    }
+                    countdown = start_countdown(config, &screen, &game);
+                }
+                stats.pause();
+                recording.record(&game, Event::Pause);
+            }
+            Input::Resize => {
+                let status = screen.status();
+                screen = Screen::new(grid_width, grid_height, theme);
+                new_size = None;
+                too_small = !output::fits(grid_width, grid_height);
+                if too_small {
+                    // Pause until the grid fits again.
+                    if !paused && !game.is_over() {
+                        paused = true;
+                        stats.pause();
+                        recording.record(&game, Event::Pause);
+                    }
+                    screen.draw_message("Terminal too small, make it bigger to continue");
+                    continue;
+                }
+                draw_game(&screen, &game, humans);
+                if let Some(lines) = &summary {
+                    screen.draw_box(lines);
+                }
+                let size = output::max_grid_size();
+                if config.fit_grid && size != (grid_width, grid_height) {
+                    let (width, height) = size;
+                    new_size = Some(size);
+                    screen.draw_status(format!(
+                        "Press ENTER for a new game on a {}x{} grid",
+                        width, height
+                    ));
+                } else if paused && !game.is_over() {
+                    screen.draw_status(String::from("Paused, press SPACE to continue"));
+                    paused_status.get_or_insert(status);
+                } else {
+                    screen.draw_status(status);
+                }
+            }
+            Input::DecreaseSpeed => {
+                if !config.arcade {
+                    increase_interval(&interval);
+                    recording.record(&game, Event::DecreaseSpeed);
+                }
+            }
+            Input::IncreaseSpeed => {
+                if !config.arcade {
+                    decrease_interval(&interval);
+                    recording.record(&game, Event::IncreaseSpeed);
+                }
+            }
+            Input::SaveReplay => {
+                if !can_save || !game.is_over() {
+                    continue;
+                }
+                screen.draw_status(match recording.save(Path::new(""), game.seed()) {
+                    Ok(path) => format!("Replay saved to {}", path.display()),
+                    Err(e) => format!("Can't save replay: {}", e),
+                });
+            }
+            Input::Step => {
+                // Write everything recorded so far, so the replay survives a crash.
+                recording.flush();
+
+                if game.is_over() || paused {
+                    continue;
+                }
+                if let Some(deadline) = countdown {
+                    if draw_countdown(&screen, &game, deadline) {
+                        continue;
+                    }
+                    countdown = None;
+                }
+
+                // The game discards a direction if the snake can't turn that way.
+                let mut directions: Vec<Option<Direction>> =
+                    turns.iter_mut().map(TurnQueue::pop).collect();
+
+                // In autopilot mode the strategy decides where to go.
+                if let Some(strategy) = &mut autopilot {
+                    directions[0] = strategy.next_direction(&game, 0);
+                }
+                // The computer snakes come after the human players.
+                for (i, strategy) in opponents.iter_mut().enumerate() {
+                    directions[humans + i] = strategy.next_direction(&game, humans + i);
+                }
+
+                // Autopilot directions are recorded as well, so replays don't
+                // depend on the autopilot implementation.
+                if let Some(d) = directions[0] {
+                    recording.record(&game, Event::Turn(d));
+                }
+
+                let tails = tails(&game);
+                let outcomes = game.step_all(&directions);
+                draw_step(&screen, &game, humans, &tails, &outcomes);
+                stats.step(outcomes[0]);
+
+                if game.is_over() {
+                    if game.players() > 1 {
+                        let message = match winner(&game, &outcomes) {
+                            Some(player) => format!("{} wins!", player_name(player, humans)),
+                            None => String::from("Draw!"),
+                        };
+                        screen.draw_status(format!("{} Seed: {}", message, game.seed()));
+                        continue;
+                    }
+                    let message = match outcomes[0] {
+                        StepOutcome::Won => "You won!",
+                        _ => "Game over!",
+                    };
+                    let mut lines = game_summary(&game, outcomes[0], &stats);
+                    // Without a store the game isn't in the high-score table.
+                    let scores = store.map(|store| save_score(store, &replay_config, &game, name));
+                    match &scores {
+                        Some(Ok((1, _))) => lines.push(String::from("New high score!")),
+                        Some(Err(e)) => lines.push(e.clone()),
+                        _ => {}
+                    }
+                    lines.push(hints.clone());
+                    if let Some(Ok((rank, top))) = &scores {
+                        lines.push(String::new());
+                        lines.extend(high_scores(*rank, top));
+                    }
+                    screen.draw_box(&lines);
+                    summary = Some(lines);
+                    screen.draw_status(format!("{} Seed: {}", message, game.seed()));
+                } else if config.arcade && outcomes.contains(&StepOutcome::Ate) {
+                    // In arcade mode we decrease the tick interval with every food eaten
+                    // to make the game faster.
+                    decrease_interval(&interval);
+                }
+            }
+        }
+    }
+}
+
+// Returns the size of the grid of the first game.
+pub fn grid_size(config: &Config) -> (u16, u16) {
+    if config.fit_grid {
+        output::max_grid_size()
+    } else {
+        (config.grid_width, config.grid_height)
+    }
+}
+
+// Creates a game with a new seed, unless one is given. Fails if the snakes
+// don't fit on the grid or the level.
+pub fn new_game(config: &Config, grid_width: u16, grid_height: u16) -> Result<Game, String> {
+    let players = humans(config) + config.opponents as usize;
+    let seed = config.seed.unwrap_or_else(random);
+    if let Some(level) = &config.level {
+        return Game::from_level(level, config.wrap, players, seed).ok_or_else(|| {
+            format!(
+                "The level has no room for {} snakes (seed {})",
+                players, seed
+            )
+        });
+    }
+    Game::with_players(
+        grid_width.into(),
+        grid_height.into(),
+        !config.no_obstacles,
+        config.wrap,
+        players,
+        seed,
+    )
+    .ok_or_else(|| {
+        format!(
+            "The grid of {}x{} has no room for {} snakes (seed {})",
+            grid_width, grid_height, players, seed
+        )
+    })
+}
+
+// Reads a level file and checks that it can be played.
+pub fn load_level(path: &Path, wrap: bool) -> Result<Level, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Can't read level file {}: {}", path.display(), e))?;
+    let level = level::parse(&content)
+        .map_err(|e| format!("Invalid level file {}: {}", path.display(), e))?;
+
+    let (max_width, max_height) = output::max_grid_size();
+    let (width, height) = (level.width() as u16, level.height() as u16);
+    if width < output::MIN_GRID_WIDTH || height < output::MIN_GRID_HEIGHT {
+        return Err(format!(
+            "The level of {}x{} is smaller than the minimum of {}x{}",
+            width,
+            height,
+            output::MIN_GRID_WIDTH,
+            output::MIN_GRID_HEIGHT
+        ));
+    }
+    if !wrap && !level.has_border() {
+        return Err(format!(
+            "The level {} has no border, it can only be played with --wrap",
+            path.display()
+        ));
+    }
+    if width > max_width || height > max_height {
+        return Err(format!(
+            "The level of {}x{} doesn't fit on the screen of {}x{}",
+            width, height, max_width, max_height
+        ));
+    }
+    Ok(level)
+}
+
+// Returns the player with the longest snake among the ones that didn't
+// crash, or among all of them if every snake crashed. Returns None for a draw.
+fn winner(game: &Game, outcomes: &[StepOutcome]) -> Option<usize> {
+    let crashed = |i: usize| matches!(outcomes[i], StepOutcome::Crashed(_));
+    let survivors = (0..game.players()).any(|i| !crashed(i));
+    let candidates: Vec<usize> = (0..game.players())
+        .filter(|i| !survivors || !crashed(*i))
+        .collect();
+
+    let length = |i: usize| game.snakes()[i].len();
+    let longest = candidates.iter().map(|i| length(*i)).max()?;
+    match candidates.iter().filter(|i| length(**i) == longest).count() {
+        1 => candidates.into_iter().find(|i| length(*i) == longest),
+        _ => None,
+    }
+}
+
+// The directions a player typed that haven't been used yet. Every step
+// takes the next one, so turns typed in quick succession aren't lost.
+struct TurnQueue {
+    turns: VecDeque<Direction>,
+    depth: usize,
+}
+
+impl TurnQueue {
+    fn new(depth: usize) -> Self {
+        TurnQueue {
+            turns: VecDeque::with_capacity(depth),
+            depth,
+        }
+    }
+
+    // Adds a turn for the snake currently heading in the given direction.
+    // Turns the snake can't take at the time they are used are ignored. If
+    // the queue is full the latest turn is replaced.
+    fn push(&mut self, direction: Direction, current: Direction) {
+        let kept = self.turns.len().min(self.depth - 1);
+        // The direction the snake will have before taking this turn.
+        let before = match kept {
+            0 => current,
+            n => self.turns[n - 1],
+        };
+        if direction == before || direction == before.opposite() {
+            return;
+        }
+        self.turns.truncate(kept);
+        self.turns.push_back(direction);
+    }
+
+    fn pop(&mut self) -> Option<Direction> {
+        self.turns.pop_front()
+    }
+}
+
+// Creates an empty turn queue for every snake.
+fn new_turns(config: &Config, game: &Game) -> Vec<TurnQueue> {
+    (0..game.players())
+        .map(|_| TurnQueue::new(config.turn_queue.into()))
+        .collect()
+}
+
+// Returns the number of snakes steered from the keyboard.
+fn humans(config: &Config) -> usize {
+    if config.two_player {
+        2
+    } else {
+        1
+    }
+}
+
+// The human players come first, followed by the computer snakes.
+fn player_name(player: usize, humans: usize) -> String {
+    if player < humans {
+        format!("Player {}", player + 1)
+    } else {
+        format!("Computer {}", player - humans + 1)
+    }
+}
+
+// Creates the strategy for the configured autopilot. If it can't be used on
+// this grid the status line shows which one is used instead.
+fn create_strategy(
+    config: &Config,
+    game: &Game,
+    screen: &impl Renderer,
+) -> Option<Box<dyn Strategy>> {
+    let autopilot = config.autopilot?;
+    let (strategy, fallback) = strategy::create(autopilot, game);
+    if let Some(fallback) = fallback {
+        screen.draw_status(format!(
+            "Seed: {} ({} autopilot not possible, using {})",
+            game.seed(),
+            autopilot,
+            fallback
+        ));
+    }
+    Some(strategy)
+}
+
+// Starts the configured countdown and returns the time it ends, or None if
+// it is disabled.
+fn start_countdown(config: &Config, screen: &impl Renderer, game: &Game) -> Option<Instant> {
+    if config.countdown == 0 {
+        return None;
+    }
+    let deadline = Instant::now() + Duration::from_secs(config.countdown.into());
+    draw_countdown(screen, game, deadline);
+    Some(deadline)
+}
+
+// Shows the seconds left until the deadline over the grid. Once it has
+// passed the grid is redrawn and false is returned.
+fn draw_countdown(screen: &impl Renderer, game: &Game, deadline: Instant) -> bool {
+    let left = deadline.saturating_duration_since(Instant::now());
+    if left.is_zero() {
+        draw_grid(screen, game);
+        return false;
+    }
+    // Round up, so the countdown goes 3, 2, 1 and not 2, 1, 0.
+    let seconds = left.as_millis().div_ceil(1000);
+    screen.draw_box(&[String::new(), format!("  {}  ", seconds), String::new()]);
+    true
+}
+
+// Creates an A* strategy for every computer snake.
+fn create_opponents(config: &Config, game: &Game) -> Vec<Box<dyn Strategy>> {
+    (0..config.opponents)
+        .map(|_| strategy::create(Autopilot::AStar, game).0)
+        .collect()
+}
+
+// Adds the score of the finished game to the high-score table and shows the
+// best scores for the game settings. Returns a note for the status line.
+fn save_score(
+    store: &Store,
+    config: &Config,
+    game: &Game,
+    name: &str,
+) -> Result<(usize, Vec<Score>), String> {
+    let key = Key::from(config);
+    let score = Score::new(game.snake().len(), game.steps(), name);
+    let rank = store
+        .add(&key, &score)
+        .map_err(|e| format!("Can't save score: {}", e))?;
+    let top = store
+        .top(&key, HIGH_SCORES)
+        .map_err(|e| format!("Can't load scores: {}", e))?;
+    Ok((rank, top))
+}
+
+// Returns the lines of the high-score table, with a marker at the score of
+// the given rank.
+fn high_scores(rank: usize, top: &[Score]) -> Vec<String> {
+    let mut lines = vec![String::from("High scores")];
+    for (i, score) in top.iter().enumerate() {
+        // Mark the score of this game.
+        lines.push(scores::format_score(i + 1, score, i + 1 == rank));
+    }
+    lines
+}
+
+// The figures of a single player game shown when it is over.
+#[derive(Default)]
+struct Stats {
+    eaten: usize,
+    // The time played, without pauses.
+    elapsed: Duration,
+    last_step: Option<Instant>,
+}
+
+impl Stats {
+    fn step(&mut self, outcome: StepOutcome) {
+        if let StepOutcome::Ate | StepOutcome::Won = outcome {
+            self.eaten += 1;
+        }
+        let now = Instant::now();
+        if let Some(last) = self.last_step {
+            self.elapsed += now - last;
+        }
+        self.last_step = Some(now);
+    }
+
+    // The time until the next step isn't counted.
+    fn pause(&mut self) {
+        self.last_step = None;
+    }
+}
+
+// Returns the lines describing how a single player game ended.
+fn game_summary(game: &Game, outcome: StepOutcome, stats: &Stats) -> Vec<String> {
+    let title = match outcome {
+        StepOutcome::Crashed(p) => format!("Game over: {}", crash_cause(game, p)),
+        _ => String::from("You won!"),
+    };
+    let seconds = stats.elapsed.as_secs();
+    let steps_per_food = match stats.eaten {
+        0 => String::from("-"),
+        n => format!("{:.1}", game.steps() as f64 / n as f64),
+    };
+    vec![
+        title,
+        format!("Length      {}", game.snake().len()),
+        format!("Steps       {}", game.steps()),
+        format!("Time        {}:{:02}", seconds / 60, seconds % 60),
+        format!("Food eaten  {}", stats.eaten),
+        format!("Steps/food  {}", steps_per_food),
+        format!("Seed        {}", game.seed()),
+    ]
+}
+
+// Describes what the snake of a single player game crashed into.
+fn crash_cause(game: &Game, p: Point) -> &'static str {
+    let (x, y) = p;
+    let (width, height) = (game.grid().len(), game.grid()[0].len());
+    let edge = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+    if game.snake().contains(&p) {
+        "ran into itself"
+    } else if edge && !game.wraps() {
+        "hit the wall"
+    } else {
+        "hit an obstacle"
+    }
+}
+
+// Returns the keys to start a new game, save the replay and quit.
+fn key_hints(keymap: &Keymap, can_save: bool) -> String {
+    let mut hints = vec![(Input::Pause, "restart")];
+    if can_save {
+        hints.push((Input::SaveReplay, "save replay"));
+    }
+    hints.push((Input::Exit, "quit"));
+    hints
+        .into_iter()
+        .filter_map(|(input, action)| keymap.key(input).map(|key| format!("{} {}", key, action)))
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+// Records the games to the replay file, if there is one, and the current
+// game in memory, so it can be saved when it is over. Recording to the file
+// stops at the first error, which is kept until it is shown.
+struct Recording {
+    file: Option<Recorder<File>>,
+    game: Recorder<Vec<u8>>,
+    error: Option<io::Error>,
+}
+
+impl Recording {
+    fn new(file: Option<Recorder<File>>) -> Self {
+        Recording {
+            file,
+            game: Recorder::new(Vec::new()),
+            error: None,
+        }
+    }
+
+    fn start(&mut self, config: &Config, game: &Game) {
+        self.write_file(|file| file.start(config, game.seed()));
+        // Writing to memory can't fail.
+        self.game = Recorder::new(Vec::new());
+        self.game.start(config, game.seed()).unwrap();
+    }
+
+    fn record(&mut self, game: &Game, event: Event) {
+        self.write_file(|file| file.record(game.steps(), event));
+        self.game.record(game.steps(), event).unwrap();
+    }
+
+    fn flush(&mut self) {
+        self.write_file(|file| file.flush());
+    }
+
+    fn write_file(&mut self, write: impl FnOnce(&mut Recorder<File>) -> io::Result<()>) {
+        if let Some(file) = &mut self.file {
+            if let Err(e) = write(file) {
+                self.file = None;
+                self.error = Some(e);
+            }
+        }
+    }
+
+    // Returns the error that stopped the recording, once.
+    fn take_error(&mut self) -> Option<String> {
+        self.error
+            .take()
+            .map(|e| format!("Recording stopped: {}", e))
+    }
+
+    // Writes the replay of the current game to snake-<seed>.replay in the
+    // given directory and returns the path. Earlier saves of the same seed
+    // are kept, a number is added to the name of the new file instead.
+    fn save(&mut self, dir: &Path, seed: u64) -> io::Result<PathBuf> {
+        self.game.flush()?;
+        let mut path = dir.join(format!("snake-{}.replay", seed));
+        let mut n = 1;
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(self.game.get_ref())?;
+                    return Ok(path);
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    n += 1;
+                    path = dir.join(format!("snake-{}-{}.replay", seed, n));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+pub fn increase_interval(interval: &Arc<AtomicU16>) {
+    let i = interval.load(atomic::Ordering::Relaxed);
+    interval.store((i + 5).min(MAX_INTERVAL as u16), atomic::Ordering::Relaxed);
+}
+
+pub fn decrease_interval(interval: &Arc<AtomicU16>) {
+    let i = interval.load(atomic::Ordering::Relaxed);
+    if i.saturating_sub(5) > MIN_INTERVAL as u16 {
+        interval.store(i - 5, atomic::Ordering::Relaxed);
+    }
+}
+
+// The first `humans` snakes are steered from the keyboard, the others by the
+// computer.
+pub fn draw_game(screen: &impl Renderer, game: &Game, humans: usize) {
+    draw_grid(screen, game);
+    draw_steps(screen, game);
+    draw_snake_len(screen, game, humans);
+    screen.draw_status(format!("Seed: {}", game.seed()));
+}
+
+// Draws all tiles of the grid, including the snakes.
+fn draw_grid(screen: &impl Renderer, game: &Game) {
+    for (x, row) in game.grid().iter().enumerate() {
+        for (y, tile) in row.iter().enumerate() {
+            screen.draw_tile((x, y), *tile)
+        }
+    }
+    // Every snake has its own colour.
+    for (player, snake) in game.snakes().iter().enumerate() {
+        for (i, &(x, y)) in snake.iter().enumerate() {
+            if game.grid()[x][y] != Tile::Snake {
+                continue;
+            }
+            if i == 0 {
+                screen.draw_head((x, y), player);
+            } else {
+                screen.draw_snake((x, y), player);
+            }
+        }
+    }
+}
+
+// Returns the ends of all snakes, to be passed to draw_step() after the next step.
+pub fn tails(game: &Game) -> Vec<Point> {
+    game.snakes().iter().map(|s| *s.back().unwrap()).collect()
+}
+
+// Draws the tiles changed by the last step. The tails are the ends of the
+// snakes before the step.
+pub fn draw_step(
+    screen: &impl Renderer,
+    game: &Game,
+    humans: usize,
+    tails: &[Point],
+    outcomes: &[StepOutcome],
+) {
+    for (player, outcome) in outcomes.iter().enumerate() {
+        let snake = &game.snakes()[player];
+        let head = snake[0];
+        match outcome {
+            StepOutcome::Ate | StepOutcome::Won => {}
+            StepOutcome::Moved => screen.draw_tile(tails[player], Tile::Free),
+            StepOutcome::Crashed(_) => continue,
+        }
+        // The old head is part of the body now.
+        if snake.len() > 1 {
+            screen.draw_snake(snake[1], player);
+        }
+        screen.draw_head(head, player);
+    }
+    // A snake may have crashed into the tail another one just left.
+    for outcome in outcomes.iter() {
+        if let StepOutcome::Crashed(p) = outcome {
+            screen.draw_tile(*p, Tile::Crash);
+        }
+    }
+    if outcomes.contains(&StepOutcome::Ate) || outcomes.contains(&StepOutcome::Won) {
+        if let Some(food) = game.food() {
+            screen.draw_tile(food, Tile::Food);
+        }
+        draw_snake_len(screen, game, humans);
+    }
+    draw_steps(screen, game);
+}
+
+fn draw_steps(screen: &impl Renderer, game: &Game) {
+    screen.draw_text_left(format!("Steps: {}", game.steps()));
+}
+
+fn draw_snake_len(screen: &impl Renderer, game: &Game, humans: usize) {
+    if game.players() == 1 {
+        screen.draw_text_right(format!("Snake length: {}", game.snake().len()));
+        return;
+    }
+    let lengths: Vec<String> = game
+        .snakes()
+        .iter()
+        .enumerate()
+        .map(|(player, snake)| {
+            if player < humans {
+                format!("P{}: {}", player + 1, snake.len())
+            } else {
+                format!("C{}: {}", player - humans + 1, snake.len())
+            }
+        })
+        .collect();
+    screen.draw_text_right(lengths.join("  "));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::MemoryScreen;
+
+    #[test]
+    fn turn_queue_keeps_quick_turns() {
+        let mut queue = TurnQueue::new(2);
+        // A U-turn while heading east.
+        queue.push(Direction::North, Direction::East);
+        queue.push(Direction::West, Direction::East);
+        assert_eq!(queue.pop(), Some(Direction::North));
+        assert_eq!(queue.pop(), Some(Direction::West));
+        assert_eq!(queue.pop(), None);
+
+        // Turns the snake can't take are ignored.
+        queue.push(Direction::East, Direction::East);
+        queue.push(Direction::West, Direction::East);
+        queue.push(Direction::North, Direction::East);
+        queue.push(Direction::South, Direction::East);
+        assert_eq!(queue.pop(), Some(Direction::North));
+        assert_eq!(queue.pop(), None);
+
+        // When the queue is full the latest turn wins.
+        let mut queue = TurnQueue::new(1);
+        queue.push(Direction::North, Direction::East);
+        queue.push(Direction::South, Direction::East);
+        assert_eq!(queue.pop(), Some(Direction::South));
+    }
+
+    #[test]
+    fn winner_is_the_survivor() {
+        let game = Game::with_players(20, 15, false, false, 2, 1).unwrap();
+        let crashed = StepOutcome::Crashed((0, 0));
+        assert_eq!(winner(&game, &[StepOutcome::Moved, crashed]), Some(0));
+        assert_eq!(winner(&game, &[crashed, StepOutcome::Ate]), Some(1));
+        // Both snakes have the same length.
+        assert_eq!(winner(&game, &[crashed, crashed]), None);
+    }
+
+    #[test]
+    fn draw_scripted_game() {
+        let mut level = Level::new(12, 11);
+        level.food = Some((8, 5));
+        let mut game = Game::from_level(&level, false, 1, 1).unwrap();
+        let screen = MemoryScreen::new(28, 13, 12, 11, &Theme::builtin("ascii").unwrap());
+        draw_game(&screen, &game, 1);
+        // Eat the food and crash into the top wall.
+        let script = [Direction::East, Direction::East, Direction::North];
+        for d in script.into_iter().chain([Direction::North; 4]) {
+            let tails = tails(&game);
+            let outcomes = game.step_all(&[Some(d)]);
+            draw_step(&screen, &game, 1, &tails, &outcomes);
+        }
+        screen.commit();
+        assert_eq!(
+            screen.frame().lines().collect::<Vec<_>>(),
+            [
+                "  Steps: 7 Snake length: 3",
+                "  ################XX######",
+                "  ##              @@    ##",
+                "  ##              oo    ##",
+                "  ##              oo    ##",
+                "  ##                    ##",
+                "  ##                    ##",
+                "  ##              **    ##",
+                "  ##                    ##",
+                "  ##                    ##",
+                "  ##                    ##",
+                "  ########################",
+                "  Seed: 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn summary_after_crash() {
+        let mut level = Level::new(12, 11);
+        level.food = Some((8, 5));
+        let mut game = Game::from_level(&level, false, 1, 1).unwrap();
+        let mut stats = Stats::default();
+        let mut outcome = StepOutcome::Moved;
+        for d in [Direction::East, Direction::East, Direction::North] {
+            outcome = game.step(Some(d));
+            stats.step(outcome);
+        }
+        while !game.is_over() {
+            outcome = game.step(None);
+            stats.step(outcome);
+        }
+        assert_eq!(
+            game_summary(&game, outcome, &stats),
+            [
+                "Game over: hit the wall",
+                "Length      3",
+                "Steps       7",
+                "Time        0:00",
+                "Food eaten  1",
+                "Steps/food  7.0",
+                "Seed        1",
+            ]
+        );
+
+        let keymap = Keymap::default();
+        assert_eq!(
+            key_hints(&keymap, true),
+            "space restart  r save replay  q quit"
+        );
+        assert_eq!(key_hints(&keymap, false), "space restart  q quit");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn recording_stops_on_error() {
+        // Every write to /dev/full fails because the disk is full.
+        let file = File::create("/dev/full").unwrap();
+        let mut recording = Recording::new(Some(Recorder::new(file)));
+        let game = Game::new(20, 15, false, false, 1);
+        recording.record(&game, Event::Pause);
+        recording.flush();
+        assert!(recording.file.is_none());
+        assert!(recording
+            .take_error()
+            .unwrap()
+            .starts_with("Recording stopped: "));
+        assert!(recording.take_error().is_none());
+        // The game is still recorded in memory.
+        recording.record(&game, Event::Quit);
+        recording.flush();
+        assert!(recording.take_error().is_none());
+    }
+
+    #[test]
+    fn save_keeps_earlier_replays() {
+        let dir = std::env::temp_dir().join(format!("snake-saves-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let game = Game::new(20, 15, false, false, 1);
+        let mut recording = Recording::new(None);
+        recording.start(&Config::default(), &game);
+        let first = recording.save(&dir, 1).unwrap();
+        let second = recording.save(&dir, 1).unwrap();
+        assert_eq!(first, dir.join("snake-1.replay"));
+        assert_eq!(second, dir.join("snake-1-2.replay"));
+        assert_eq!(fs::read(&first).unwrap(), fs::read(&second).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
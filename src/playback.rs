@@ -0,0 +1,348 @@
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{self, AtomicU16};
+use std::sync::mpsc;
+use std::sync::Arc;
+
+use snake::config::MIN_INTERVAL;
+use snake::game::{Game, StepOutcome};
+use snake::replay::{self, Event, Replay};
+use snake::types::Direction;
+
+use crate::cli::MAX_INTERVAL;
+use crate::input::{self, Input};
+use crate::keymap::Keymap;
+use crate::output::{self, Renderer, Screen};
+use crate::play::{decrease_interval, draw_game, draw_step, increase_interval, tails};
+use crate::theme::Theme;
+
+// Reads the replay file and returns the requested game. Games are counted
+// from 1, without a number the last game in the file is returned.
+pub fn load(path: &Path, game: Option<usize>) -> Result<Replay, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Can't read replay file {}: {}", path.display(), e))?;
+    let mut replays = replay::parse(&content)
+        .map_err(|e| format!("Invalid replay file {}: {}", path.display(), e))?;
+
+    let count = replays.len();
+    let index = game.unwrap_or(count);
+    if index == 0 || index > count {
+        return Err(format!(
+            "Replay file {} contains {} game(s), can't play game {}",
+            path.display(),
+            count,
+            index
+        ));
+    }
+    let replay = replays.swap_remove(index - 1);
+
+    let (width, height) = (replay.config.grid_width, replay.config.grid_height);
+    if width < output::MIN_GRID_WIDTH || height < output::MIN_GRID_HEIGHT {
+        return Err(format!(
+            "The recorded grid of {}x{} is smaller than the minimum of {}x{}",
+            width,
+            height,
+            output::MIN_GRID_WIDTH,
+            output::MIN_GRID_HEIGHT
+        ));
+    }
+    let interval = replay.config.interval;
+    if !(MIN_INTERVAL..=MAX_INTERVAL).contains(&interval.into()) {
+        return Err(format!(
+            "The recorded interval of {} ms is outside of {}-{} ms",
+            interval, MIN_INTERVAL, MAX_INTERVAL
+        ));
+    }
+    let (max_width, max_height) = output::max_grid_size();
+    if replay.config.grid_width > max_width || replay.config.grid_height > max_height {
+        return Err(format!(
+            "The recorded grid of {}x{} doesn't fit on the screen",
+            replay.config.grid_width, replay.config.grid_height
+        ));
+    }
+
+    Ok(replay)
+}
+
+pub fn run(replay: &Replay, start: u32, keymap: Keymap, theme: &Theme) {
+    let interval = Arc::new(AtomicU16::new(replay.config.interval));
+
+    let mut player = Player::new(replay, Arc::clone(&interval));
+    player.seek(start);
+    let mut paused = start > 0;
+    // The step to jump to, as typed by the user.
+    let mut jump = String::new();
+
+    let mut screen = Screen::new(replay.config.grid_width, replay.config.grid_height, theme);
+    draw_game(&screen, player.game(), 1);
+    draw_status(&screen, &player, paused, &jump);
+
+    let (tx, rx) = mpsc::channel();
+
+    // Spawn thread to handle ui input.
+    input::handle(tx.clone(), keymap);
+
+    // Spawn thread to send ticks.
+    input::send_ticks(tx, Arc::clone(&interval));
+
+    // Set while the grid doesn't fit on the screen.
+    let mut too_small = false;
+
+    loop {
+        screen.commit();
+        let input = rx.recv().unwrap();
+        if too_small && !matches!(input, Input::Resize | Input::Exit) {
+            continue;
+        }
+        match input {
+            Input::Unknown => continue,
+            Input::Exit => break,
+            Input::Pause => paused ^= true,
+            Input::DecreaseSpeed => increase_interval(&interval),
+            Input::IncreaseSpeed => decrease_interval(&interval),
+            // While paused the arrow keys move forward and backward one step at a time.
+            Input::ChangeDirection(_, Direction::East) if paused => advance(&screen, &mut player),
+            Input::ChangeDirection(_, Direction::West) if paused => {
+                let step = player.game().steps().saturating_sub(1);
+                player.seek(step);
+                screen = redraw(replay, &player, theme);
+            }
+            Input::ChangeDirection(..) | Input::SaveReplay => continue,
+            Input::Digit(d) => jump.push(char::from(b'0' + d)),
+            Input::Enter => {
+                if let Ok(step) = jump.parse() {
+                    player.seek(step);
+                    screen = redraw(replay, &player, theme);
+                    paused = true;
+                }
+                jump.clear();
+            }
+            Input::Resize => {
+                screen = redraw(replay, &player, theme);
+                too_small = !output::fits(replay.config.grid_width, replay.config.grid_height);
+                if too_small {
+                    paused = true;
+                    screen.draw_message("Terminal too small, make it bigger to continue");
+                    continue;
+                }
+            }
+            Input::Step => {
+                if paused || player.is_finished() {
+                    continue;
+                }
+                advance(&screen, &mut player);
+            }
+        }
+        draw_status(&screen, &player, paused, &jump);
+    }
+}
+
+fn advance(screen: &impl Renderer, player: &mut Player) {
+    let tails = tails(player.game());
+    if let Some(outcome) = player.step() {
+        draw_step(screen, player.game(), 1, &tails, &[outcome]);
+    }
+}
+
+// Clears the screen and draws the current state of the game.
+fn redraw(replay: &Replay, player: &Player, theme: &Theme) -> Screen {
+    let screen = Screen::new(replay.config.grid_width, replay.config.grid_height, theme);
+    draw_game(&screen, player.game(), 1);
+    screen
+}
+
+fn draw_status(screen: &impl Renderer, player: &Player, paused: bool, jump: &str) {
+    let seed = player.game().seed();
+    if !jump.is_empty() {
+        screen.draw_status(format!("Jump to step: {}", jump));
+    } else if player.is_finished() {
+        screen.draw_status(format!("End of replay. Seed: {}", seed));
+    } else if paused {
+        screen.draw_status(format!("Replay paused. Seed: {}", seed));
+    } else {
+        screen.draw_status(format!("Replay. Seed: {}", seed));
+    }
+}
+
+// Re-drives a recorded game by feeding the recorded inputs to a new game
+// with the same seed.
+struct Player<'a> {
+    replay: &'a Replay,
+    game: Game,
+    // Index of the next event to process.
+    next: usize,
+    quit: bool,
+    interval: Arc<AtomicU16>,
+}
+
+impl<'a> Player<'a> {
+    fn new(replay: &'a Replay, interval: Arc<AtomicU16>) -> Self {
+        Player {
+            replay,
+            game: new_game(replay),
+            next: 0,
+            quit: false,
+            interval,
+        }
+    }
+
+    fn game(&self) -> &Game {
+        &self.game
+    }
+
+    // The replay is finished when the snake crashed or the player quit.
+    fn is_finished(&self) -> bool {
+        self.quit || self.game.is_over()
+    }
+
+    // Processes the inputs recorded before the next step and advances the game.
+    fn step(&mut self) -> Option<StepOutcome> {
+        if self.is_finished() {
+            return None;
+        }
+
+        let mut direction = None;
+        while let Some((step, event)) = self.replay.events.get(self.next) {
+            if *step > self.game.steps() {
+                break;
+            }
+            self.next += 1;
+            match event {
+                Event::Turn(d) => direction = Some(*d),
+                Event::IncreaseSpeed => decrease_interval(&self.interval),
+                Event::DecreaseSpeed => increase_interval(&self.interval),
+                Event::Pause => {}
+                Event::Quit => {
+                    self.quit = true;
+                    return None;
+                }
+            }
+        }
+
+        let outcome = self.game.step(direction);
+        if outcome == StepOutcome::Ate && self.replay.config.arcade {
+            decrease_interval(&self.interval);
+        }
+        Some(outcome)
+    }
+
+    // Moves the game to the given step, or as close as the replay gets.
+    // Going backwards restarts the game and plays it up to the step.
+    fn seek(&mut self, step: u32) {
+        if step < self.game.steps() {
+            self.game = new_game(self.replay);
+            self.next = 0;
+            self.quit = false;
+            self.interval
+                .store(self.replay.config.interval, atomic::Ordering::Relaxed);
+        }
+        while self.game.steps() < step && self.step().is_some() {}
+    }
+}
+
+fn new_game(replay: &Replay) -> Game {
+    Game::new(
+        replay.config.grid_width.into(),
+        replay.config.grid_height.into(),
+        !replay.config.no_obstacles,
+        replay.config.wrap,
+        replay.seed(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use snake::config::Config;
+    use snake::replay::Recorder;
+
+    fn test_config() -> Config {
+        Config {
+            interval: 100,
+            seed: Some(3),
+            ..Config::default()
+        }
+    }
+
+    // Plays a game with a few turns, records it and returns the recording
+    // together with the final game.
+    fn recorded_game() -> (Replay, Game) {
+        let config = test_config();
+        let mut game = Game::new(20, 15, true, false, 3);
+        let mut out = Vec::new();
+        let mut recorder = Recorder::new(&mut out);
+        recorder.start(&config, game.seed()).unwrap();
+
+        let turns = [Direction::North, Direction::West, Direction::South];
+        while !game.is_over() && game.steps() < 30 {
+            let direction = turns.get(game.steps() as usize / 3).copied();
+            if let Some(d) = direction {
+                recorder.record(game.steps(), Event::Turn(d)).unwrap();
+            }
+            game.step(direction);
+        }
+        recorder.record(game.steps(), Event::Quit).unwrap();
+        recorder.flush().unwrap();
+        drop(recorder);
+
+        let replays = replay::parse(&String::from_utf8(out).unwrap()).unwrap();
+        (replays.into_iter().next().unwrap(), game)
+    }
+
+    #[test]
+    fn player_replays_game() {
+        let (replay, game) = recorded_game();
+        let mut player = Player::new(&replay, Arc::new(AtomicU16::new(100)));
+        while player.step().is_some() {}
+        assert!(player.is_finished());
+        assert_eq!(player.game().steps(), game.steps());
+        assert_eq!(player.game().snake(), game.snake());
+        assert_eq!(player.game().grid(), game.grid());
+    }
+
+    #[test]
+    fn player_seeks_backwards() {
+        let (replay, _) = recorded_game();
+        let mut player = Player::new(&replay, Arc::new(AtomicU16::new(100)));
+        player.seek(5);
+        let snake = player.game().snake().clone();
+        player.seek(8);
+        player.seek(5);
+        assert_eq!(player.game().steps(), 5);
+        assert_eq!(player.game().snake(), &snake);
+    }
+
+    // Records a game with the given settings and loads it.
+    fn load_recorded(config: &Config) -> Result<Replay, String> {
+        let path = std::env::temp_dir().join(format!("snake-replay-{}", std::process::id()));
+        let mut recorder = Recorder::create(&path).unwrap();
+        recorder.start(config, 3).unwrap();
+        recorder.record(0, Event::Quit).unwrap();
+        recorder.flush().unwrap();
+        drop(recorder);
+        let replay = load(&path, None);
+        fs::remove_file(&path).unwrap();
+        replay
+    }
+
+    #[test]
+    fn load_rejects_invalid_settings() {
+        let small = Config {
+            grid_width: 6,
+            grid_height: 6,
+            ..test_config()
+        };
+        assert_eq!(
+            load_recorded(&small).unwrap_err(),
+            "The recorded grid of 6x6 is smaller than the minimum of 12x11"
+        );
+        let fast = Config {
+            interval: 3,
+            ..test_config()
+        };
+        assert_eq!(
+            load_recorded(&fast).unwrap_err(),
+            "The recorded interval of 3 ms is outside of 30-300 ms"
+        );
+    }
+}
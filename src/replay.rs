@@ -0,0 +1,374 @@
+//! Recording and loading of replay files.
+//!
+//! A replay file is a plain text file with one entry per line. Every game
+//! starts with a version line followed by the seed and the settings of the
+//! game. After that each input that was processed by the game is written as
+//! the number of steps taken so far, followed by the input:
+//!
+//! ```text
+//! snake-replay 1
+//! seed 42
+//! grid_width 20
+//! grid_height 15
+//! no_obstacles false
+//! wrap false
+//! autopilot off
+//! arcade false
+//! interval 175
+//! countdown 3
+//! turn_queue 2
+//! 0 turn north
+//! 12 faster
+//! 17 pause
+//! 17 pause
+//! 30 quit
+//! ```
+//!
+//! Settings missing from the header, like in files written before they were
+//! recorded, get their default value, except for the seed, the grid size
+//! and the interval. A file can hold several games in a row, each starting
+//! with its own version line.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::config::Config;
+use crate::types::Direction;
+
+/// Version of the replay format written by the [`Recorder`].
+pub const VERSION: u32 = 1;
+
+const MAGIC: &str = "snake-replay";
+
+/// An input processed by the game.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Event {
+    /// The snake was steered in the given direction for the next step.
+    Turn(Direction),
+    /// The game was paused or resumed.
+    Pause,
+    /// The snake was made faster.
+    IncreaseSpeed,
+    /// The snake was made slower.
+    DecreaseSpeed,
+    /// The player quit the game.
+    Quit,
+}
+
+/// A recorded game.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    /// The settings of the game. The grid size is the one actually used,
+    /// `fit_grid` is always false and `seed` is always set.
+    pub config: Config,
+    /// The inputs of the game together with the number of steps taken
+    /// before they were processed, in the order they happened.
+    pub events: Vec<(u32, Event)>,
+}
+
+impl Replay {
+    /// Returns the seed of the recorded game.
+    pub fn seed(&self) -> u64 {
+        self.config.seed.unwrap_or_default()
+    }
+
+    /// Returns the number of steps the recording covers. Unless the snake
+    /// crashed this is the step of the last recorded input.
+    pub fn last_step(&self) -> u32 {
+        self.events.last().map_or(0, |(step, _)| *step)
+    }
+}
+
+/// Writes games to a replay file while they are played.
+///
+/// Events are buffered and only written on [`Recorder::flush`], which the
+/// game loop calls on every step. So the file is always complete up to the
+/// last step, even if the program is killed.
+pub struct Recorder<W: Write> {
+    out: BufWriter<W>,
+}
+
+impl Recorder<File> {
+    /// Creates (or truncates) the replay file at the given path.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Recorder::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    /// Creates a recorder writing to the given writer.
+    pub fn new(out: W) -> Self {
+        Recorder {
+            out: BufWriter::new(out),
+        }
+    }
+
+    /// Starts the recording of a new game. The grid size in the config must
+    /// be the one actually used for the game.
+    pub fn start(&mut self, config: &Config, seed: u64) -> io::Result<()> {
+        writeln!(self.out, "{} {}", MAGIC, VERSION)?;
+        writeln!(self.out, "seed {}", seed)?;
+        writeln!(self.out, "grid_width {}", config.grid_width)?;
+        writeln!(self.out, "grid_height {}", config.grid_height)?;
+        writeln!(self.out, "no_obstacles {}", config.no_obstacles)?;
+        writeln!(self.out, "wrap {}", config.wrap)?;
+        match config.autopilot {
+            Some(autopilot) => writeln!(self.out, "autopilot {}", autopilot)?,
+            None => writeln!(self.out, "autopilot off")?,
+        }
+        writeln!(self.out, "arcade {}", config.arcade)?;
+        writeln!(self.out, "interval {}", config.interval)?;
+        writeln!(self.out, "countdown {}", config.countdown)?;
+        writeln!(self.out, "turn_queue {}", config.turn_queue)?;
+        self.out.flush()
+    }
+
+    /// Records an event that was processed after the given number of steps.
+    pub fn record(&mut self, step: u32, event: Event) -> io::Result<()> {
+        match event {
+            Event::Turn(d) => writeln!(self.out, "{} turn {}", step, direction_name(d)),
+            Event::Pause => writeln!(self.out, "{} pause", step),
+            Event::IncreaseSpeed => writeln!(self.out, "{} faster", step),
+            Event::DecreaseSpeed => writeln!(self.out, "{} slower", step),
+            Event::Quit => writeln!(self.out, "{} quit", step),
+        }
+    }
+
+    /// Writes all recorded events to the file.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    /// Returns the writer the recording goes to. Events that weren't
+    /// flushed yet aren't written to it.
+    pub fn get_ref(&self) -> &W {
+        self.out.get_ref()
+    }
+}
+
+/// An error in a replay file.
+#[derive(Debug, Eq, PartialEq)]
+pub struct ParseError {
+    /// The line number, starting at 1.
+    pub line: usize,
+    /// What is wrong with the line.
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses the content of a replay file and returns all games in it.
+pub fn parse(s: &str) -> Result<Vec<Replay>, ParseError> {
+    let mut replays: Vec<Replay> = Vec::new();
+
+    for (i, line) in s.lines().enumerate() {
+        let err = |message: String| ParseError {
+            line: i + 1,
+            message,
+        };
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+
+        if fields[0] == MAGIC {
+            match fields.get(1).map(|v| v.parse::<u32>()) {
+                Some(Ok(VERSION)) if fields.len() == 2 => {}
+                Some(Ok(v)) if v != VERSION => {
+                    return Err(err(format!("unsupported replay version {}", v)))
+                }
+                _ => return Err(err(format!("expected `{} {}`", MAGIC, VERSION))),
+            }
+            replays.push(Replay {
+                // The entries every header must have start out as 0.
+                config: Config {
+                    grid_width: 0,
+                    grid_height: 0,
+                    interval: 0,
+                    ..Config::default()
+                },
+                events: Vec::new(),
+            });
+            continue;
+        }
+
+        let replay = replays
+            .last_mut()
+            .ok_or_else(|| err(format!("expected `{} {}`", MAGIC, VERSION)))?;
+        let config = &mut replay.config;
+        match fields.as_slice() {
+            ["seed", v] => config.seed = Some(parse_value(v).map_err(err)?),
+            ["grid_width", v] => config.grid_width = parse_value(v).map_err(err)?,
+            ["grid_height", v] => config.grid_height = parse_value(v).map_err(err)?,
+            ["no_obstacles", v] => config.no_obstacles = parse_value(v).map_err(err)?,
+            ["wrap", v] => config.wrap = parse_value(v).map_err(err)?,
+            ["autopilot", "off"] => config.autopilot = None,
+            ["autopilot", v] => config.autopilot = Some(parse_value(v).map_err(err)?),
+            ["arcade", v] => config.arcade = parse_value(v).map_err(err)?,
+            ["interval", v] => config.interval = parse_value(v).map_err(err)?,
+            ["countdown", v] => config.countdown = parse_value(v).map_err(err)?,
+            ["turn_queue", v] => config.turn_queue = parse_value(v).map_err(err)?,
+            [step, event @ ..] if step.starts_with(|c: char| c.is_ascii_digit()) => {
+                let step: u32 = parse_value(step).map_err(err)?;
+                if step < replay.last_step() {
+                    return Err(err(format!("step {} is before the previous entry", step)));
+                }
+                let event = match event {
+                    ["turn", d] => Event::Turn(
+                        parse_direction(d)
+                            .ok_or_else(|| err(format!("invalid direction `{}`", d)))?,
+                    ),
+                    ["pause"] => Event::Pause,
+                    ["faster"] => Event::IncreaseSpeed,
+                    ["slower"] => Event::DecreaseSpeed,
+                    ["quit"] => Event::Quit,
+                    _ => return Err(err(format!("invalid event `{}`", event.join(" ")))),
+                };
+                replay.events.push((step, event));
+            }
+            _ => return Err(err(format!("invalid entry `{}`", line.trim()))),
+        }
+    }
+
+    for replay in replays.iter() {
+        if replay.config.seed.is_none()
+            || replay.config.grid_width == 0
+            || replay.config.grid_height == 0
+            || replay.config.interval == 0
+        {
+            return Err(ParseError {
+                line: s.lines().count(),
+                message: String::from("incomplete replay header"),
+            });
+        }
+    }
+
+    Ok(replays)
+}
+
+fn parse_value<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value `{}`", value))
+}
+
+fn direction_name(d: Direction) -> &'static str {
+    match d {
+        Direction::North => "north",
+        Direction::South => "south",
+        Direction::West => "west",
+        Direction::East => "east",
+    }
+}
+
+fn parse_direction(s: &str) -> Option<Direction> {
+    Direction::ALL.into_iter().find(|d| direction_name(*d) == s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Autopilot;
+
+    fn test_config() -> Config {
+        Config {
+            autopilot: Some(Autopilot::Cycle),
+            arcade: true,
+            wrap: true,
+            interval: 150,
+            countdown: 0,
+            turn_queue: 1,
+            seed: Some(7),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn record_and_parse() {
+        let mut out = Vec::new();
+        let mut recorder = Recorder::new(&mut out);
+        recorder.start(&test_config(), 7).unwrap();
+        recorder.record(0, Event::Turn(Direction::North)).unwrap();
+        recorder.record(3, Event::Pause).unwrap();
+        recorder.record(3, Event::IncreaseSpeed).unwrap();
+        recorder.start(&test_config(), 8).unwrap();
+        recorder.record(5, Event::Quit).unwrap();
+        recorder.flush().unwrap();
+        drop(recorder);
+
+        let replays = parse(&String::from_utf8(out).unwrap()).unwrap();
+        assert_eq!(replays.len(), 2);
+        assert_eq!(replays[0].seed(), 7);
+        assert_eq!(replays[0].config.grid_width, 20);
+        assert_eq!(replays[0].config.interval, 150);
+        assert_eq!(replays[0].config.countdown, 0);
+        assert_eq!(replays[0].config.turn_queue, 1);
+        assert!(replays[0].config.arcade);
+        assert!(replays[0].config.wrap);
+        assert_eq!(replays[0].config.autopilot, Some(Autopilot::Cycle));
+        assert_eq!(
+            replays[0].events,
+            vec![
+                (0, Event::Turn(Direction::North)),
+                (3, Event::Pause),
+                (3, Event::IncreaseSpeed)
+            ]
+        );
+        assert_eq!(replays[1].seed(), 8);
+        assert_eq!(replays[1].last_step(), 5);
+
+        // Older files don't record every setting.
+        let replays =
+            parse("snake-replay 1\nseed 1\ngrid_width 20\ngrid_height 15\ninterval 175").unwrap();
+        assert_eq!(replays[0].config.countdown, Config::default().countdown);
+        assert_eq!(replays[0].config.turn_queue, Config::default().turn_queue);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            parse("seed 1").unwrap_err(),
+            ParseError {
+                line: 1,
+                message: String::from("expected `snake-replay 1`")
+            }
+        );
+        assert_eq!(
+            parse("snake-replay 2").unwrap_err().message,
+            "unsupported replay version 2"
+        );
+        assert_eq!(
+            parse("snake-replay 1\nseed 1\ngrid_width 20\n4 turn up")
+                .unwrap_err()
+                .to_string(),
+            "line 4: invalid direction `up`"
+        );
+        assert_eq!(
+            parse("snake-replay 1\nseed x").unwrap_err().message,
+            "invalid value `x`"
+        );
+        assert_eq!(
+            parse("snake-replay 1\ngrid_width 20").unwrap_err().message,
+            "incomplete replay header"
+        );
+        assert_eq!(
+            parse("snake-replay 1\nseed 1\ngrid_width 20\ngrid_height 15")
+                .unwrap_err()
+                .message,
+            "incomplete replay header"
+        );
+        assert_eq!(
+            parse("snake-replay 1\nseed 1\n4 jump").unwrap_err().message,
+            "invalid event `jump`"
+        );
+    }
+}
@@ -0,0 +1,30 @@
+use std::io;
+
+use snake::scores::{self, Key, Store};
+
+// Prints the best scores of every game configuration.
+pub fn print(store: &Store, count: usize) -> io::Result<()> {
+    let scores = store.load()?;
+    if scores.is_empty() {
+        println!("No high scores yet.");
+        return Ok(());
+    }
+
+    let mut last: Option<Key> = None;
+    let mut rank = 0;
+    for (key, score) in scores.iter() {
+        if last != Some(*key) {
+            if last.is_some() {
+                println!();
+            }
+            println!("{}", key);
+            last = Some(*key);
+            rank = 0;
+        }
+        rank += 1;
+        if rank <= count {
+            println!("{}", scores::format_score(rank, score, false));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,470 @@
+//! The high-score table.
+//!
+//! Scores are kept in a plain text file, one score per line, and are only
+//! compared with scores of games played with the same settings (see
+//! [`Key`]). Every line holds the settings followed by the score, the name
+//! of the player comes last as it may contain spaces:
+//!
+//! ```text
+//! snake-scores 2
+//! 20 15 true false false off 175 42 310 2026-10-16 Jane Doe
+//! ```
+//!
+//! Files of version 1, written before wrapping grids were part of the key,
+//! are read as scores without wrapping and upgraded when a score is added.
+//!
+//! New scores are appended while the file is locked, so several games can
+//! end at the same time without losing a score.
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{Autopilot, Config};
+
+/// Version of the high-score file format.
+pub const VERSION: u32 = 2;
+
+const MAGIC: &str = "snake-scores";
+
+/// The settings a score was achieved with. Scores are only ranked against
+/// scores with the same key.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Key {
+    /// Width of the grid, including the border.
+    pub grid_width: u16,
+    /// Height of the grid, including the border.
+    pub grid_height: u16,
+    /// Whether obstacles were placed on the grid.
+    pub obstacles: bool,
+    /// Whether the game was played in arcade mode.
+    pub arcade: bool,
+    /// Whether the snake could leave the grid on one side and come back on
+    /// the other.
+    pub wrap: bool,
+    /// The autopilot that controlled the snake, if any.
+    pub autopilot: Option<Autopilot>,
+    /// The snake advance interval the game started with, in ms.
+    pub interval: u16,
+}
+
+impl From<&Config> for Key {
+    /// The grid size in the config must be the one actually used for the game.
+    fn from(config: &Config) -> Self {
+        Key {
+            grid_width: config.grid_width,
+            grid_height: config.grid_height,
+            obstacles: !config.no_obstacles,
+            arcade: config.arcade,
+            wrap: config.wrap,
+            autopilot: config.autopilot,
+            interval: config.interval,
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.grid_width, self.grid_height)?;
+        if !self.obstacles {
+            write!(f, ", no obstacles")?;
+        }
+        if self.arcade {
+            write!(f, ", arcade")?;
+        }
+        if self.wrap {
+            write!(f, ", wrap")?;
+        }
+        if let Some(autopilot) = self.autopilot {
+            write!(f, ", autopilot {}", autopilot)?;
+        }
+        write!(f, ", interval {} ms", self.interval)
+    }
+}
+
+/// A single entry of the high-score table.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Score {
+    /// The final length of the snake.
+    pub length: usize,
+    /// The number of steps the game took.
+    pub steps: u32,
+    /// The day the game was played on, as `YYYY-MM-DD` in UTC.
+    pub date: String,
+    /// The name of the player.
+    pub name: String,
+}
+
+impl Score {
+    /// Creates a score for a game that ended today.
+    pub fn new(length: usize, steps: u32, name: &str) -> Self {
+        let days = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() / 86400);
+        Score {
+            length,
+            steps,
+            date: format_date(days as i64),
+            // The name is the last field of a line.
+            name: name.replace(['\n', '\r'], " ").trim().to_string(),
+        }
+    }
+
+    // Longer snakes are better, for the same length fewer steps are better.
+    fn beats(&self, other: &Score) -> bool {
+        (self.length, other.steps) > (other.length, self.steps)
+    }
+}
+
+/// The high-score file.
+pub struct Store {
+    path: PathBuf,
+}
+
+impl Store {
+    /// Uses the high-score file at the given path. It is created when the
+    /// first score is added.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Store {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Returns the default location of the high-score file in the data
+    /// directory of the user, `$XDG_DATA_HOME/snake/scores` on Linux.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("snake").join("scores"))
+    }
+
+    /// Returns the path of the high-score file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Adds a score and returns its rank among the scores with the same
+    /// key, starting at 1. Ties go to the older score.
+    pub fn add(&self, key: &Key, score: &Score) -> io::Result<usize> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+        // The lock is released when the file is closed.
+        file.lock()?;
+
+        let (version, scores) = read(&mut file)?;
+        let mut entry = String::new();
+        if file.metadata()?.len() == 0 || version != VERSION {
+            // Files of an older version are written again in the current one.
+            file.set_len(0)?;
+            entry.push_str(&format!("{} {}\n", MAGIC, VERSION));
+            for (k, s) in &scores {
+                entry.push_str(&format_entry(k, s));
+            }
+        }
+        entry.push_str(&format_entry(key, score));
+        file.write_all(entry.as_bytes())?;
+
+        let better = scores
+            .iter()
+            .filter(|(k, s)| k == key && !score.beats(s))
+            .count();
+        Ok(better + 1)
+    }
+
+    /// Returns all scores, grouped by key and best first within a group.
+    pub fn load(&self) -> io::Result<Vec<(Key, Score)>> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        file.lock_shared()?;
+        let (_, mut scores) = read(&mut file)?;
+        // The sort is stable, so of two equal scores the older one comes first.
+        scores.sort_by(|(a_key, a), (b_key, b)| {
+            a_key
+                .cmp(b_key)
+                .then(b.length.cmp(&a.length))
+                .then(a.steps.cmp(&b.steps))
+        });
+        Ok(scores)
+    }
+
+    /// Returns the best scores for the given key, at most `count` of them.
+    pub fn top(&self, key: &Key, count: usize) -> io::Result<Vec<Score>> {
+        Ok(self
+            .load()?
+            .into_iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, score)| score)
+            .take(count)
+            .collect())
+    }
+}
+
+// Reads the scores and the version of the file.
+fn read(file: &mut File) -> io::Result<(u32, Vec<(Key, Score)>)> {
+    let mut content = String::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_string(&mut content)?;
+    parse(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn format_entry(key: &Key, score: &Score) -> String {
+    format!(
+        "{} {} {} {} {} {} {} {} {} {} {}\n",
+        key.grid_width,
+        key.grid_height,
+        key.obstacles,
+        key.arcade,
+        key.wrap,
+        key.autopilot.map_or(String::from("off"), |a| a.to_string()),
+        key.interval,
+        score.length,
+        score.steps,
+        score.date,
+        score.name
+    )
+}
+
+// Returns the version of the file and its scores. An empty file has the
+// current version.
+fn parse(s: &str) -> Result<(u32, Vec<(Key, Score)>), String> {
+    let mut version = VERSION;
+    let mut scores = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let err = |message: &str| format!("line {}: {}", i + 1, message);
+        let mut fields: Vec<&str> = line.split_whitespace().collect();
+        if i == 0 {
+            version = match fields[..] {
+                [MAGIC, v] => v.parse().ok().filter(|v| (1..=VERSION).contains(v)),
+                _ => None,
+            }
+            .ok_or_else(|| err(&format!("expected `{} {}`", MAGIC, VERSION)))?;
+            continue;
+        }
+        if fields.is_empty() {
+            continue;
+        }
+        // Version 1 had no wrap field, those grids didn't wrap.
+        if version == 1 && fields.len() >= 4 {
+            fields.insert(4, "false");
+        }
+        if fields.len() < 10 {
+            return Err(err("incomplete entry"));
+        }
+        let entry =
+            parse_entry(&fields).ok_or_else(|| err(&format!("invalid entry `{}`", line.trim())))?;
+        scores.push(entry);
+    }
+    Ok((version, scores))
+}
+
+fn parse_entry(fields: &[&str]) -> Option<(Key, Score)> {
+    let key = Key {
+        grid_width: fields[0].parse().ok()?,
+        grid_height: fields[1].parse().ok()?,
+        obstacles: fields[2].parse().ok()?,
+        arcade: fields[3].parse().ok()?,
+        wrap: fields[4].parse().ok()?,
+        autopilot: match fields[5] {
+            "off" => None,
+            v => Some(v.parse().ok()?),
+        },
+        interval: fields[6].parse().ok()?,
+    };
+    let score = Score {
+        length: fields[7].parse().ok()?,
+        steps: fields[8].parse().ok()?,
+        date: fields[9].to_string(),
+        name: fields[10..].join(" "),
+    };
+    Some((key, score))
+}
+
+/// Formats a line of the high-score table for the score with the given
+/// rank, starting at 1. A marked line starts with `>` instead of a space.
+/// The date comes last, so narrow tables cut it off before the name.
+pub fn format_score(rank: usize, score: &Score, marked: bool) -> String {
+    format!(
+        "{}{:>2}. {:>4} {:>6} steps  {:<16}  {}",
+        if marked { '>' } else { ' ' },
+        rank,
+        score.length,
+        score.steps,
+        score.name,
+        score.date
+    )
+}
+
+// Formats the number of days since 1970-01-01 as a date, see
+// <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn format_date(days: i64) -> String {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn test_key(arcade: bool) -> Key {
+        Key {
+            grid_width: 20,
+            grid_height: 15,
+            obstacles: true,
+            arcade,
+            wrap: false,
+            autopilot: None,
+            interval: 175,
+        }
+    }
+
+    fn test_store(name: &str) -> Store {
+        let path = env::temp_dir()
+            .join(format!("snake-scores-{}", process::id()))
+            .join(name);
+        let _ = fs::remove_file(&path);
+        Store::new(path)
+    }
+
+    #[test]
+    fn add_and_rank_scores() {
+        let store = test_store("rank");
+        let key = test_key(false);
+        assert_eq!(store.add(&key, &Score::new(10, 100, "a")).unwrap(), 1);
+        assert_eq!(store.add(&key, &Score::new(20, 300, "b")).unwrap(), 1);
+        assert_eq!(
+            store.add(&key, &Score::new(20, 200, "Jane Doe")).unwrap(),
+            1
+        );
+        assert_eq!(store.add(&key, &Score::new(20, 200, "d")).unwrap(), 2);
+        // Scores of other settings don't count.
+        assert_eq!(
+            store.add(&test_key(true), &Score::new(5, 50, "e")).unwrap(),
+            1
+        );
+
+        let top = store.top(&key, 3).unwrap();
+        let names: Vec<&str> = top.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["Jane Doe", "d", "b"]);
+        assert_eq!(store.load().unwrap().len(), 5);
+        fs::remove_file(store.path()).unwrap();
+    }
+
+    #[test]
+    fn add_concurrently() {
+        let store = test_store("concurrent");
+        let key = test_key(false);
+        std::thread::scope(|s| {
+            for i in 0..8 {
+                let store = &store;
+                s.spawn(move || {
+                    for j in 0..10 {
+                        store.add(&key, &Score::new(i * 10 + j, 1, "a")).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(store.load().unwrap().len(), 80);
+        fs::remove_file(store.path()).unwrap();
+    }
+
+    #[test]
+    fn load_missing_file() {
+        let store = test_store("missing");
+        assert!(store.load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            parse("scores").unwrap_err(),
+            "line 1: expected `snake-scores 2`"
+        );
+        assert_eq!(
+            parse("snake-scores 3").unwrap_err(),
+            "line 1: expected `snake-scores 2`"
+        );
+        assert_eq!(
+            parse("snake-scores 2\n20 15 true").unwrap_err(),
+            "line 2: incomplete entry"
+        );
+        assert_eq!(
+            parse("snake-scores 2\n20 15 yes false false off 175 3 10 2026-01-01 a").unwrap_err(),
+            "line 2: invalid entry `20 15 yes false false off 175 3 10 2026-01-01 a`"
+        );
+    }
+
+    #[test]
+    fn wrap_is_part_of_the_key() {
+        let store = test_store("wrap");
+        let key = test_key(false);
+        let wrap = Key { wrap: true, ..key };
+        assert_eq!(store.add(&key, &Score::new(10, 100, "a")).unwrap(), 1);
+        assert_eq!(store.add(&wrap, &Score::new(5, 100, "b")).unwrap(), 1);
+        assert_eq!(store.top(&wrap, 5).unwrap()[0].name, "b");
+        assert_eq!(wrap.to_string(), "20x15, wrap, interval 175 ms");
+        fs::remove_file(store.path()).unwrap();
+    }
+
+    #[test]
+    fn upgrade_version_1() {
+        let store = test_store("version-1");
+        fs::create_dir_all(store.path().parent().unwrap()).unwrap();
+        fs::write(
+            store.path(),
+            "snake-scores 1\n20 15 true false off 175 42 310 2026-10-16 Jane Doe\n",
+        )
+        .unwrap();
+        let key = test_key(false);
+        assert_eq!(store.top(&key, 5).unwrap()[0].name, "Jane Doe");
+
+        assert_eq!(store.add(&key, &Score::new(10, 100, "a")).unwrap(), 2);
+        let content = fs::read_to_string(store.path()).unwrap();
+        assert!(content.starts_with("snake-scores 2\n20 15 true false false off 175 42 310"));
+        assert_eq!(store.load().unwrap().len(), 2);
+        fs::remove_file(store.path()).unwrap();
+    }
+
+    #[test]
+    fn format_scores() {
+        let score = Score {
+            length: 42,
+            steps: 310,
+            date: String::from("2026-10-16"),
+            name: String::from("Jane Doe"),
+        };
+        assert_eq!(
+            format_score(1, &score, false),
+            "  1.   42    310 steps  Jane Doe          2026-10-16"
+        );
+        assert_eq!(
+            format_score(10, &score, true),
+            ">10.   42    310 steps  Jane Doe          2026-10-16"
+        );
+    }
+
+    #[test]
+    fn format_dates() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(11016), "2000-02-29");
+        assert_eq!(format_date(20742), "2026-10-16");
+    }
+}
@@ -0,0 +1,251 @@
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use snake::config::{Autopilot, MIN_INTERVAL};
+
+use crate::cli::{self, Opts, MAX_INTERVAL};
+use crate::keymap::{Keymap, PRESETS};
+use crate::theme::{self, Theme, ThemeSettings};
+
+// Defaults for the options of a game, read from a TOML file:
+//
+//   interval = 150
+//   grid-width = 30
+//   grid-height = 20
+//   fit-grid = false
+//   obstacles = true
+//   autopilot = "off"
+//   arcade = false
+//   keymap = "vim"
+//   theme = "ascii"
+//
+//   [keys]
+//   pause = ["p", "space"]
+//
+//   [themes.mine]
+//   food = { glyph = "*", fg = "red" }
+//
+// Every key is optional. Options given on the command line win over the file.
+// The keys table binds actions to other keys than the keymap does, the themes
+// tables define themes in addition to the built-in ones.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Settings {
+    interval: Option<u16>,
+    grid_width: Option<u16>,
+    grid_height: Option<u16>,
+    fit_grid: Option<bool>,
+    obstacles: Option<bool>,
+    // A strategy or "off".
+    autopilot: Option<String>,
+    arcade: Option<bool>,
+    keymap: Option<String>,
+    keys: Option<BTreeMap<String, Vec<String>>>,
+    theme: Option<String>,
+    themes: Option<BTreeMap<String, ThemeSettings>>,
+}
+
+// Returns the default location of the settings file in the config directory
+// of the user, `$XDG_CONFIG_HOME/snake/config.toml` on Linux.
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("snake").join("config.toml"))
+}
+
+// Reads the settings from the given file, or from the default one if it
+// exists.
+pub fn load(path: Option<&Path>) -> Result<Settings, String> {
+    let (path, required) = match path {
+        Some(path) => (path.to_path_buf(), true),
+        None => match default_path() {
+            Some(path) => (path, false),
+            None => return Ok(Settings::default()),
+        },
+    };
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+            return Ok(Settings::default())
+        }
+        Err(e) => return Err(format!("Can't read config file {}: {}", path.display(), e)),
+    };
+    parse(&content).map_err(|e| format!("Invalid config file {}: {}", path.display(), e))
+}
+
+fn parse(s: &str) -> Result<Settings, String> {
+    let settings: Settings = toml::from_str(s).map_err(|e| e.to_string())?;
+
+    // The same checks as for the command line options.
+    if let Some(interval) = settings.interval {
+        if !(MIN_INTERVAL..MAX_INTERVAL).contains(&interval.into()) {
+            return Err(format!(
+                "interval: Interval not in range {}-{}",
+                MIN_INTERVAL,
+                MAX_INTERVAL - 1
+            ));
+        }
+    }
+    if let Some(width) = settings.grid_width {
+        cli::grid_width_in_range(&width.to_string()).map_err(|e| format!("grid-width: {}", e))?;
+    }
+    if let Some(height) = settings.grid_height {
+        cli::grid_height_in_range(&height.to_string())
+            .map_err(|e| format!("grid-height: {}", e))?;
+    }
+    if let Some(autopilot) = &settings.autopilot {
+        if autopilot != "off" {
+            autopilot
+                .parse::<Autopilot>()
+                .map_err(|e| format!("autopilot: {}", e))?;
+        }
+    }
+    if let Some(name) = &settings.keymap {
+        if !PRESETS.contains(&name.as_str()) {
+            return Err(format!(
+                "keymap: unknown keymap `{}`, expected one of {}",
+                name,
+                PRESETS.join(", ")
+            ));
+        }
+    }
+    let mut keymap = Keymap::default();
+    for (action, keys) in settings.keys.iter().flatten() {
+        keymap
+            .rebind(action, keys)
+            .map_err(|e| format!("keys.{}: {}", action, e))?;
+    }
+    for (name, theme) in settings.themes.iter().flatten() {
+        theme
+            .theme()
+            .map_err(|e| format!("themes.{}: {}", name, e))?;
+    }
+    if let Some(name) = &settings.theme {
+        settings
+            .theme(Some(name))
+            .map_err(|e| format!("theme: {}", e))?;
+    }
+    Ok(settings)
+}
+
+impl Settings {
+    // Returns the keymap with the given name, or the one from the file,
+    // with the keys from the file bound.
+    pub fn keymap(&self, name: Option<&str>) -> Keymap {
+        let mut keymap = name
+            .or(self.keymap.as_deref())
+            .and_then(Keymap::preset)
+            .unwrap_or_default();
+        for (action, keys) in self.keys.iter().flatten() {
+            // The keys are checked when the file is parsed.
+            keymap.rebind(action, keys).unwrap();
+        }
+        keymap
+    }
+
+    // Returns the theme with the given name, or the one from the file. The
+    // themes defined in the file hide the built-in ones of the same name.
+    // Without a name the theme is picked to suit the terminal.
+    pub fn theme(&self, name: Option<&str>) -> Result<Theme, String> {
+        let Some(name) = name.or(self.theme.as_deref()) else {
+            return Ok(Theme::detect());
+        };
+        match self.themes.as_ref().and_then(|themes| themes.get(name)) {
+            // The themes are checked when the file is parsed.
+            Some(settings) => Ok(settings.theme().unwrap()),
+            None => Theme::builtin(name).ok_or_else(|| theme::unknown_theme(name)),
+        }
+    }
+
+    // Sets the options that weren't given on the command line to the values
+    // from the file.
+    pub fn apply(&self, opts: &mut Opts, matches: &ArgMatches) {
+        let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        if let Some(interval) = self.interval.filter(|_| !given("interval")) {
+            opts.interval = interval;
+        }
+        // A grid size on the command line replaces fit-grid from the file.
+        let grid_given = given("grid_width") || given("grid_height");
+        if let Some(fit_grid) = self.fit_grid.filter(|_| !grid_given) {
+            opts.fit_grid = fit_grid;
+        }
+        if let Some(width) = self.grid_width.filter(|_| !given("grid_width")) {
+            opts.grid_width = width;
+        }
+        if let Some(height) = self.grid_height.filter(|_| !given("grid_height")) {
+            opts.grid_height = height;
+        }
+        if let Some(obstacles) = self.obstacles.filter(|_| !given("no_obstacles")) {
+            opts.no_obstacles = !obstacles;
+        }
+        // Two players can't use the autopilot.
+        let autopilot_given = given("autopilot") || given("two_player");
+        if let Some(autopilot) = self.autopilot.as_ref().filter(|_| !autopilot_given) {
+            opts.autopilot = autopilot.parse().ok();
+        }
+        if let Some(arcade) = self.arcade.filter(|_| !given("arcade")) {
+            opts.arcade = arcade;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_settings() {
+        let settings = parse("interval = 100\narcade = true\nautopilot = \"off\"\n").unwrap();
+        assert_eq!(settings.interval, Some(100));
+        assert_eq!(settings.arcade, Some(true));
+        assert_eq!(settings.autopilot.as_deref(), Some("off"));
+        assert_eq!(settings.grid_width, None);
+        assert_eq!(parse("").unwrap(), Settings::default());
+
+        let settings =
+            parse("theme = \"mine\"\n[themes.mine]\nbase = \"ascii\"\nfood = { glyph = \"$\" }")
+                .unwrap();
+        let mine = settings.theme(None).unwrap();
+        assert_ne!(mine, Theme::builtin("ascii").unwrap());
+        // A theme given on the command line wins.
+        assert_eq!(
+            settings.theme(Some("monochrome")).unwrap(),
+            Theme::builtin("monochrome").unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse("speed = 100")
+            .unwrap_err()
+            .contains("unknown field `speed`"));
+        assert!(parse("arcade = 1").unwrap_err().contains("invalid type"));
+        assert_eq!(
+            parse("interval = 10").unwrap_err(),
+            "interval: Interval not in range 30-299"
+        );
+        assert_eq!(
+            parse("keymap = \"emacs\"").unwrap_err(),
+            "keymap: unknown keymap `emacs`, expected one of arrows, vim, wasd"
+        );
+        assert_eq!(
+            parse("[keys]\npause = [\"p\", \"hyper-p\"]").unwrap_err(),
+            "keys.pause: unknown key `hyper-p`"
+        );
+        assert_eq!(
+            parse("autopilot = \"dfs\"").unwrap_err(),
+            "autopilot: unknown autopilot `dfs`"
+        );
+        assert!(parse("theme = \"solarized\"")
+            .unwrap_err()
+            .starts_with("theme: unknown theme `solarized`"));
+        assert_eq!(
+            parse("[themes.mine]\nfood = { glyph = \"*\", fg = \"pink\" }").unwrap_err(),
+            "themes.mine: food: unknown colour `pink`"
+        );
+    }
+}
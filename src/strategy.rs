@@ -0,0 +1,207 @@
+//! Strategies for the autopilot.
+//!
+//! A [`Strategy`] looks at the game before every step and picks the
+//! direction the snake should move in. The game loop only knows about the
+//! trait, so a new strategy needs nothing but an implementation, a variant
+//! of [`Autopilot`] to select it and an entry in the table of [`create`].
+
+use std::borrow::Cow;
+
+use crate::config::Autopilot;
+use crate::cycle::Cycle;
+use crate::game::{generate_successors, next_point, Game};
+use crate::path;
+use crate::types::{Direction, Grid, Point, Tile};
+
+/// Steers a snake when the computer is in control.
+pub trait Strategy {
+    /// Returns the name of the strategy as shown to the user.
+    fn name(&self) -> &'static str;
+
+    /// Returns the direction for the next step of the snake of `player`.
+    /// `None` keeps the snake moving in its current direction.
+    ///
+    /// The strategy is called once before every step of a single game and
+    /// always for the same player, so it may keep state between the calls.
+    fn next_direction(&mut self, game: &Game, player: usize) -> Option<Direction>;
+}
+
+// Builds a strategy for the game, None if it can't be used on its grid.
+type Build = fn(&Game) -> Option<Box<dyn Strategy>>;
+
+// Every strategy with the name it is selected by.
+pub(crate) const STRATEGIES: [(Autopilot, &str, Build); 2] = [
+    (Autopilot::AStar, "astar", |_| {
+        Some(Box::new(AStar::default()))
+    }),
+    (Autopilot::Cycle, "cycle", |game| {
+        Some(Box::new(Cycle::build(game.grid())?))
+    }),
+];
+
+// Works on every grid, so it replaces the strategies that don't.
+const FALLBACK: Autopilot = Autopilot::AStar;
+
+/// Creates the strategy selected by `autopilot` for the given game.
+///
+/// Some strategies don't work on every grid. In that case the A* strategy
+/// is returned instead, together with the autopilot it stands for.
+pub fn create(autopilot: Autopilot, game: &Game) -> (Box<dyn Strategy>, Option<Autopilot>) {
+    let build = |autopilot: Autopilot| {
+        let (_, _, build) = STRATEGIES.iter().find(|(a, _, _)| *a == autopilot)?;
+        build(game)
+    };
+    match build(autopilot) {
+        Some(strategy) => (strategy, None),
+        None => (build(FALLBACK).unwrap(), Some(FALLBACK)),
+    }
+}
+
+/// Chases the food on the shortest path that leaves the snake a way out,
+/// see [`path::find_safe`].
+///
+/// Other snakes are obstacles, as are the tiles right in front of their
+/// heads. The path is planned again when the food was eaten by another snake
+/// or another snake moved into the way.
+#[derive(Default)]
+pub struct AStar {
+    // The remaining directions to the food, in reverse order.
+    path: Vec<Direction>,
+    // The food the path leads to.
+    target: Option<Point>,
+}
+
+impl Strategy for AStar {
+    fn name(&self) -> &'static str {
+        "astar"
+    }
+
+    fn next_direction(&mut self, game: &Game, player: usize) -> Option<Direction> {
+        let food = game.food()?;
+        let snake = &game.snakes()[player];
+        let grid = obstacle_grid(game, player);
+
+        let blocked = self.path.last().is_some_and(|d| {
+            let next = next_point(*snake.front().unwrap(), *d, &grid, game.wraps());
+            path::blocked_tile(&grid, next)
+        });
+        if self.target != Some(food) || blocked {
+            self.path.clear();
+        }
+        if self.path.is_empty() {
+            self.path = path::find_safe(&grid, snake, food, game.wraps());
+            self.target = Some(food);
+        }
+        // Pop the next direction from the path.
+        // If it is empty (no path found), continue in the current
+        // direction and try again after the next step.
+        self.path.pop()
+    }
+}
+
+// Returns the grid as seen by the snake of `player`. The free tiles other
+// snakes can move to with the next step are obstacles, to avoid head-on
+// collisions. The food is left alone, it is worth the risk.
+fn obstacle_grid(game: &Game, player: usize) -> Cow<'_, Grid> {
+    if game.players() == 1 {
+        return Cow::Borrowed(game.grid());
+    }
+    let mut grid = game.grid().clone();
+    for (i, snake) in game.snakes().iter().enumerate() {
+        if i == player {
+            continue;
+        }
+        for (x, y) in generate_successors(*snake.front().unwrap(), game.grid(), game.wraps()) {
+            if grid[x][y] == Tile::Free {
+                grid[x][y] = Tile::Obstacle;
+            }
+        }
+    }
+    Cow::Owned(grid)
+}
+
+impl Strategy for Cycle {
+    fn name(&self) -> &'static str {
+        "cycle"
+    }
+
+    fn next_direction(&mut self, game: &Game, player: usize) -> Option<Direction> {
+        Cycle::next_direction(self, game.grid(), &game.snakes()[player], game.food())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{create_grid, StepOutcome};
+    use std::collections::VecDeque;
+
+    fn play(strategy: &mut dyn Strategy, game: &mut Game) -> StepOutcome {
+        loop {
+            let direction = strategy.next_direction(game, 0);
+            match game.step(direction) {
+                StepOutcome::Moved | StepOutcome::Ate => {}
+                outcome => return outcome,
+            }
+        }
+    }
+
+    #[test]
+    fn create_selects_strategy() {
+        let game = Game::new(12, 12, false, false, 1);
+        assert_eq!(create(Autopilot::AStar, &game).0.name(), "astar");
+        let (cycle, fallback) = create(Autopilot::Cycle, &game);
+        assert_eq!((cycle.name(), fallback), ("cycle", None));
+        // A grid with an odd number of free tiles has no Hamiltonian cycle.
+        let game = Game::new(13, 13, false, false, 1);
+        let (astar, fallback) = create(Autopilot::Cycle, &game);
+        assert_eq!((astar.name(), fallback), ("astar", Some(Autopilot::AStar)));
+    }
+
+    #[test]
+    fn cycle_fills_the_grid() {
+        let mut game = Game::new(12, 12, false, false, 3);
+        let (mut strategy, _) = create(Autopilot::Cycle, &game);
+        assert_eq!(play(strategy.as_mut(), &mut game), StepOutcome::Won);
+    }
+
+    // A game on a 11x11 grid with the snake in the middle, heading west.
+    fn game_with_food(food: Point) -> Game {
+        let mut grid = create_grid(11, 11);
+        grid[5][5] = Tile::Snake;
+        grid[6][5] = Tile::Snake;
+        grid[food.0][food.1] = Tile::Food;
+        let snake = VecDeque::from([(5, 5), (6, 5)]);
+        Game::from_parts(grid, snake, food, false, 0)
+    }
+
+    #[test]
+    fn astar_replans_for_new_food() {
+        let mut strategy = AStar::default();
+        assert_eq!(
+            strategy.next_direction(&game_with_food((5, 1)), 0),
+            Some(Direction::North)
+        );
+        // Someone else ate the food and it reappeared on the other side.
+        assert_eq!(
+            strategy.next_direction(&game_with_food((5, 9)), 0),
+            Some(Direction::South)
+        );
+    }
+
+    #[test]
+    fn astar_avoids_other_heads() {
+        let game = Game::with_players(20, 15, false, false, 2, 1).unwrap();
+        let grid = obstacle_grid(&game, 0);
+        let own = *game.snakes()[0].front().unwrap();
+        let other = *game.snakes()[1].front().unwrap();
+        for (x, y) in generate_successors(other, game.grid(), false) {
+            if game.grid()[x][y] == Tile::Free {
+                assert_eq!(grid[x][y], Tile::Obstacle);
+            }
+        }
+        for (x, y) in generate_successors(own, game.grid(), false) {
+            assert_ne!(grid[x][y], Tile::Obstacle);
+        }
+    }
+}
@@ -0,0 +1,361 @@
+use crossterm::style::{Attribute, Color, ContentStyle, StyledContent};
+use serde::Deserialize;
+use std::env;
+
+use snake::types::Tile;
+
+// Names of the built-in themes.
+pub const THEMES: [&str; 5] = [
+    "classic",
+    "high-contrast",
+    "colour-blind",
+    "monochrome",
+    "ascii",
+];
+
+// The snakes of the players after the fourth one look like the fourth.
+const PLAYERS: usize = 4;
+
+// The characters drawn for a tile and their style.
+#[derive(Clone, Debug, PartialEq)]
+struct Symbol {
+    glyph: String,
+    style: ContentStyle,
+}
+
+impl Symbol {
+    fn new(glyph: &str, foreground: Option<Color>, background: Option<Color>) -> Self {
+        let mut style = ContentStyle::new();
+        style.foreground_color = foreground;
+        style.background_color = background;
+        // Tiles are two characters wide, a single character is doubled.
+        let glyph = match glyph.chars().count() {
+            1 => glyph.repeat(2),
+            _ => String::from(glyph),
+        };
+        Symbol { glyph, style }
+    }
+
+    // A symbol drawn in reverse video, for themes without colours.
+    fn reverse(glyph: &str) -> Self {
+        let mut symbol = Symbol::new(glyph, None, None);
+        symbol.style.attributes.set(Attribute::Reverse);
+        symbol
+    }
+
+    fn styled(&self) -> StyledContent<&str> {
+        StyledContent::new(self.style, self.glyph.as_str())
+    }
+}
+
+// How the tiles and snakes are drawn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    free: Symbol,
+    food: Symbol,
+    obstacle: Symbol,
+    crash: Symbol,
+    // The body and the head of the snake of every player.
+    snakes: [Symbol; PLAYERS],
+    heads: [Symbol; PLAYERS],
+}
+
+impl Theme {
+    // Returns the built-in theme with the given name.
+    pub fn builtin(name: &str) -> Option<Self> {
+        let plain = |glyph: &str| Symbol::new(glyph, None, None);
+        let fg = |glyph: &str, color: Color| Symbol::new(glyph, Some(color), None);
+        let (free, food, obstacle, crash, snakes) = match name {
+            "classic" => (
+                plain("  "),
+                fg("██", Color::Yellow),
+                fg("▓▓", Color::White),
+                Symbol::new("XX", Some(Color::Red), Some(Color::White)),
+                [
+                    fg("██", Color::Green),
+                    fg("██", Color::Blue),
+                    fg("██", Color::Magenta),
+                    fg("██", Color::Cyan),
+                ],
+            ),
+            // Bright colours on a black background.
+            "high-contrast" => (
+                Symbol::new("  ", None, Some(Color::Black)),
+                Symbol::new("██", Some(Color::Yellow), Some(Color::Black)),
+                Symbol::new("▒▒", Some(Color::White), Some(Color::Black)),
+                Symbol::new("XX", Some(Color::White), Some(Color::Red)),
+                [
+                    Symbol::new("██", Some(Color::Green), Some(Color::Black)),
+                    Symbol::new("██", Some(Color::Cyan), Some(Color::Black)),
+                    Symbol::new("██", Some(Color::Magenta), Some(Color::Black)),
+                    Symbol::new("██", Some(Color::Blue), Some(Color::Black)),
+                ],
+            ),
+            // The Okabe-Ito palette, which stays distinguishable with the
+            // common kinds of colour blindness.
+            "colour-blind" => (
+                plain("  "),
+                fg("██", rgb(230, 159, 0)),
+                fg("▓▓", Color::Grey),
+                Symbol::new("XX", Some(rgb(213, 94, 0)), Some(Color::White)),
+                [
+                    fg("██", rgb(0, 114, 178)),
+                    fg("██", rgb(86, 180, 233)),
+                    fg("██", rgb(204, 121, 167)),
+                    fg("██", rgb(0, 158, 115)),
+                ],
+            ),
+            // No colours, the players differ by the shade of their snake.
+            "monochrome" => (
+                plain("  "),
+                plain("()"),
+                plain("▓▓"),
+                Symbol::reverse("XX"),
+                [plain("██"), plain("▒▒"), plain("░░"), plain("▞▞")],
+            ),
+            "ascii" => {
+                let heads = [(); PLAYERS].map(|_| plain("@@"));
+                return Some(Theme {
+                    free: plain("  "),
+                    food: plain("**"),
+                    obstacle: plain("##"),
+                    crash: plain("XX"),
+                    snakes: [plain("oo"), plain("++"), plain("=="), plain("%%")],
+                    heads,
+                });
+            }
+            _ => return None,
+        };
+        Some(Theme {
+            free,
+            food,
+            obstacle,
+            crash,
+            heads: snakes.clone(),
+            snakes,
+        })
+    }
+
+    // Returns the theme to use when none is configured: ASCII if colours are
+    // disabled with NO_COLOR or the terminal may not show Unicode, classic
+    // otherwise.
+    pub fn detect() -> Self {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let name = if no_color || !utf8_locale() {
+            "ascii"
+        } else {
+            "classic"
+        };
+        Theme::builtin(name).unwrap()
+    }
+
+    // Returns the characters to be drawn for the given tile. A Tile::Snake
+    // is drawn like the body of the first player.
+    pub fn tile(&self, tile: Tile) -> StyledContent<&str> {
+        match tile {
+            Tile::Free => &self.free,
+            Tile::Snake => &self.snakes[0],
+            Tile::Food => &self.food,
+            Tile::Obstacle => &self.obstacle,
+            Tile::Crash => &self.crash,
+        }
+        .styled()
+    }
+
+    // Returns the characters for the body of the snake of the given player.
+    pub fn snake(&self, player: usize) -> StyledContent<&str> {
+        self.snakes[player.min(PLAYERS - 1)].styled()
+    }
+
+    // Returns the characters for the head of the snake of the given player.
+    pub fn head(&self, player: usize) -> StyledContent<&str> {
+        self.heads[player.min(PLAYERS - 1)].styled()
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::builtin(THEMES[0]).unwrap()
+    }
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color::Rgb { r, g, b }
+}
+
+// Returns true if the locale of the environment uses UTF-8. The first of
+// these variables that is set decides, like in the C library.
+fn utf8_locale() -> bool {
+    // The Windows console doesn't use these variables.
+    if cfg!(windows) {
+        return true;
+    }
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default()
+        .to_lowercase();
+    locale.contains("utf-8") || locale.contains("utf8")
+}
+
+// A theme defined in the config file:
+//
+//   [themes.mine]
+//   base = "ascii"
+//   snake = { glyph = "o", fg = "green" }
+//   food = { glyph = "*", fg = "#e69f00", bg = "black" }
+//
+// Tiles that aren't given look like in the base theme, classic by default.
+// A head looks like its body when only the body is given. Only the head of
+// the first player can be set on its own.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeSettings {
+    base: Option<String>,
+    free: Option<SymbolSettings>,
+    snake: Option<SymbolSettings>,
+    head: Option<SymbolSettings>,
+    food: Option<SymbolSettings>,
+    obstacle: Option<SymbolSettings>,
+    crash: Option<SymbolSettings>,
+    // The snakes of the other players.
+    p2: Option<SymbolSettings>,
+    p3: Option<SymbolSettings>,
+    p4: Option<SymbolSettings>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct SymbolSettings {
+    glyph: String,
+    fg: Option<String>,
+    bg: Option<String>,
+}
+
+impl SymbolSettings {
+    fn symbol(&self) -> Result<Symbol, String> {
+        let count = self.glyph.chars().count();
+        if !(1..=2).contains(&count) {
+            return Err(format!(
+                "glyph `{}` must be one or two characters",
+                self.glyph
+            ));
+        }
+        let color = |c: &Option<String>| c.as_deref().map(parse_color).transpose();
+        Ok(Symbol::new(&self.glyph, color(&self.fg)?, color(&self.bg)?))
+    }
+}
+
+impl ThemeSettings {
+    // Builds the theme, or describes what is wrong with it.
+    pub fn theme(&self) -> Result<Theme, String> {
+        let base = self.base.as_deref().unwrap_or(THEMES[0]);
+        let mut theme = Theme::builtin(base).ok_or_else(|| unknown_theme(base))?;
+        let tiles = [
+            (&self.free, &mut theme.free, "free"),
+            (&self.food, &mut theme.food, "food"),
+            (&self.obstacle, &mut theme.obstacle, "obstacle"),
+            (&self.crash, &mut theme.crash, "crash"),
+        ];
+        let [snake, p2, p3, p4] = &mut theme.snakes;
+        let snakes = [
+            (&self.snake, snake, "snake"),
+            (&self.p2, p2, "p2"),
+            (&self.p3, p3, "p3"),
+            (&self.p4, p4, "p4"),
+        ];
+        for (settings, symbol, name) in tiles.into_iter().chain(snakes) {
+            if let Some(settings) = settings {
+                *symbol = settings.symbol().map_err(|e| format!("{}: {}", name, e))?;
+            }
+        }
+        let given = [&self.snake, &self.p2, &self.p3, &self.p4];
+        for (i, settings) in given.iter().enumerate() {
+            if settings.is_some() {
+                theme.heads[i] = theme.snakes[i].clone();
+            }
+        }
+        if let Some(head) = &self.head {
+            theme.heads[0] = head.symbol().map_err(|e| format!("head: {}", e))?;
+        }
+        Ok(theme)
+    }
+}
+
+// Parses a colour name like `dark_green` or a hex colour like `#00ff00`.
+fn parse_color(s: &str) -> Result<Color, String> {
+    let err = || format!("unknown colour `{}`", s);
+    if let Some(hex) = s.strip_prefix('#') {
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(err());
+        }
+        let value = u32::from_str_radix(hex, 16).unwrap();
+        let [_, r, g, b] = value.to_be_bytes();
+        return Ok(rgb(r, g, b));
+    }
+    Color::try_from(s).map_err(|_| err())
+}
+
+pub fn unknown_theme(name: &str) -> String {
+    format!(
+        "unknown theme `{}`, expected one of {} or a theme from the config file",
+        name,
+        THEMES.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_themes() {
+        for name in THEMES {
+            let theme = Theme::builtin(name).unwrap();
+            for tile in [Tile::Free, Tile::Food, Tile::Obstacle, Tile::Crash] {
+                assert_eq!(theme.tile(tile).content().chars().count(), 2);
+            }
+        }
+        let ascii = Theme::builtin("ascii").unwrap();
+        assert_eq!(*ascii.head(0).content(), "@@");
+        assert_eq!(*ascii.snake(0).content(), "oo");
+        assert_eq!(*ascii.tile(Tile::Food).content(), "**");
+        assert_eq!(*ascii.tile(Tile::Obstacle).content(), "##");
+        // The fifth player looks like the fourth.
+        assert_eq!(ascii.snake(4), ascii.snake(3));
+        assert!(Theme::builtin("solarized").is_none());
+    }
+
+    #[test]
+    fn user_theme() {
+        let settings: ThemeSettings =
+            toml::from_str("base = \"ascii\"\nsnake = { glyph = \"s\", fg = \"#00ff00\" }")
+                .unwrap();
+        let theme = settings.theme().unwrap();
+        assert_eq!(*theme.snake(0).content(), "ss");
+        assert_eq!(
+            theme.snake(0).style().foreground_color,
+            Some(rgb(0, 255, 0))
+        );
+        // The head follows the body, the other tiles the base theme.
+        assert_eq!(theme.head(0), theme.snake(0));
+        assert_eq!(*theme.tile(Tile::Food).content(), "**");
+
+        let error = |s: &str| {
+            toml::from_str::<ThemeSettings>(s)
+                .unwrap()
+                .theme()
+                .unwrap_err()
+        };
+        assert_eq!(
+            error("food = { glyph = \"***\" }"),
+            "food: glyph `***` must be one or two characters"
+        );
+        assert_eq!(
+            error("p2 = { glyph = \"x\", bg = \"pink\" }"),
+            "p2: unknown colour `pink`"
+        );
+        assert!(error("base = \"solarized\"").starts_with("unknown theme `solarized`"));
+    }
+}
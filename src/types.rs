@@ -1,26 +1,47 @@
-use int_enum::IntEnum;
+//! Basic types shared by the game, the pathfinding and the UI.
+
 use std::collections::VecDeque;
 
+/// The content of a single tile on the grid.
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Tile {
+    /// Nothing on this tile.
     Free,
+    /// Part of the snake.
     Snake,
+    /// The food the snake is looking for.
     Food,
+    /// A wall or an obstacle.
     Obstacle,
+    /// The point where the snake crashed.
     Crash,
 }
 
+/// The direction the snake is heading. North is up.
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, Eq, PartialEq, IntEnum)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Direction {
+    /// Up.
     North = 0,
+    /// Down.
     South = 1,
+    /// Left.
     West = 2,
+    /// Right.
     East = 3,
 }
 
 impl Direction {
+    /// All directions.
+    pub const ALL: [Direction; 4] = [
+        Direction::North,
+        Direction::South,
+        Direction::West,
+        Direction::East,
+    ];
+
+    /// Returns the opposite direction.
     pub fn opposite(&self) -> Direction {
         match self {
             Direction::North => Direction::South,
@@ -31,6 +52,9 @@ impl Direction {
     }
 }
 
+/// A position on the grid as `(x, y)`, with `(0, 0)` in the top left corner.
 pub type Point = (usize, usize);
+/// The points of a snake, head first.
 pub type Snake = VecDeque<Point>;
+/// The grid of tiles, indexed as `grid[x][y]`.
 pub type Grid = Vec<Vec<Tile>>;